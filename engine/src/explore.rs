@@ -0,0 +1,162 @@
+//! Batch runs over every turn string of a given length, each run is classified
+//! from the growth of its bounding box, the symmetry of its cells and the cycle detector.
+
+use crate::gamemodes::cycle::Cycle;
+use crate::gamemodes::langton::{Ant, Direction, Gamestate, Rule, RuleError, Turn};
+use crate::grid::ChunkedGrid;
+use std::fmt;
+
+// Bounding box growth exponent below which a pattern is considered bounded
+const BOUNDED_GROWTH: f64 = 0.05;
+// Share of cells matching their mirror image above which a pattern is symmetric
+const SYMMETRIC_SCORE: f64 = 0.9;
+
+/// Long-term behaviour of a rule
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Behaviour {
+    // The ant repeats itself while moving away
+    Highway,
+    // The pattern stops growing
+    Bounded,
+    // The pattern keeps growing as a mirror image of itself
+    Symmetric,
+    Chaotic,
+}
+
+impl fmt::Display for Behaviour {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Behaviour::Highway => "highway",
+            Behaviour::Bounded => "bounded",
+            Behaviour::Symmetric => "symmetric",
+            Behaviour::Chaotic => "chaotic",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Measurements of one rule run by a single ant
+pub struct Exploration {
+    pub notation: String,
+    pub behaviour: Behaviour,
+    pub cycle: Option<Cycle>,
+    pub painted_cells: usize,
+    // Width and height of the bounding box
+    pub size: (i64, i64),
+    // Exponent of the bounding box growth over the second half of the run, 1 for linear growth
+    pub growth: f64,
+    // Best share of cells matching their image by a reflection of the bounding box
+    pub symmetry: f64,
+}
+
+// Every turn string of 1 to max_length letters from the alphabet, shortest first
+pub fn enumerate_rules(alphabet: &[Turn], max_length: usize) -> Vec<String> {
+    let mut rules = Vec::new();
+    let mut current: Vec<String> = vec![String::new()];
+    for _ in 0..max_length {
+        current = current
+            .iter()
+            .flat_map(|prefix| {
+                alphabet
+                    .iter()
+                    .map(move |turn| format!("{}{}", prefix, turn.as_letter()))
+            })
+            .collect();
+        rules.extend(current.iter().cloned());
+    }
+    rules
+}
+
+// Run a rule from a blank grid with one ant at the origin and classify it
+pub fn explore_rule(notation: &str, steps: u64) -> Result<Exploration, RuleError> {
+    let mut gamestate = Gamestate::new();
+    let index = gamestate.add_rule(Rule::new(notation, notation, (0x000000, 0xFFFFFF))?);
+    gamestate.select_rule(index);
+    gamestate.add_ant(Ant::place_ant(0, 0, Direction::Up));
+
+    gamestate.step(steps / 2);
+    let half_size = bounding_size(gamestate.get_grid());
+    gamestate.step(steps - steps / 2);
+    let size = bounding_size(gamestate.get_grid());
+
+    let longest_side = |(width, height): (i64, i64)| width.max(height) as f64;
+    let growth = if longest_side(half_size) > 0.0 {
+        (longest_side(size) / longest_side(half_size)).log2()
+    } else {
+        0.0
+    };
+    let symmetry = symmetry(gamestate.get_grid());
+    let cycle = gamestate.get_cycle();
+
+    let behaviour = match cycle {
+        Some(cycle) if cycle.is_highway() => Behaviour::Highway,
+        Some(_) => Behaviour::Bounded,
+        None if growth < BOUNDED_GROWTH => Behaviour::Bounded,
+        None if symmetry >= SYMMETRIC_SCORE => Behaviour::Symmetric,
+        None => Behaviour::Chaotic,
+    };
+
+    Ok(Exploration {
+        notation: notation.to_string(),
+        behaviour,
+        cycle,
+        painted_cells: gamestate.get_painted_cells(),
+        size,
+        growth,
+        symmetry,
+    })
+}
+
+fn bounding_size(grid: &ChunkedGrid) -> (i64, i64) {
+    match grid.bounding_box() {
+        Some((min_x, min_y, max_x, max_y)) => (
+            max_x as i64 - min_x as i64 + 1,
+            max_y as i64 - min_y as i64 + 1,
+        ),
+        None => (0, 0),
+    }
+}
+
+// Compare the cells with their image through the center of the bounding box,
+// the two axes and, when they land on cells, the two diagonals
+fn symmetry(grid: &ChunkedGrid) -> f64 {
+    let Some((min_x, min_y, max_x, max_y)) = grid.bounding_box() else {
+        return 0.0;
+    };
+    // Twice the center, so that reflections stay on integers
+    let center_x = min_x as i64 + max_x as i64;
+    let center_y = min_y as i64 + max_y as i64;
+
+    // Each reflection maps (x, y) to (a x + b y + offset_x, c x + d y + offset_y)
+    let mut reflections = vec![
+        ((-1, 0, 0, -1), (center_x, center_y)),
+        ((-1, 0, 0, 1), (center_x, 0)),
+        ((1, 0, 0, -1), (0, center_y)),
+    ];
+    if (center_x - center_y) % 2 == 0 {
+        let difference = (center_x - center_y) / 2;
+        let sum = (center_x + center_y) / 2;
+        reflections.push(((0, 1, 1, 0), (difference, -difference)));
+        reflections.push(((0, -1, -1, 0), (sum, sum)));
+    }
+
+    let painted = grid.len();
+    reflections
+        .iter()
+        .map(|((a, b, c, d), (offset_x, offset_y))| {
+            let matching = grid
+                .iter()
+                .filter(|((x, y), state)| {
+                    let (x, y) = (*x as i64, *y as i64);
+                    let mirror_x = a * x + b * y + offset_x;
+                    let mirror_y = c * x + d * y + offset_y;
+                    i32::try_from(mirror_x)
+                        .ok()
+                        .zip(i32::try_from(mirror_y).ok())
+                        .is_some_and(|position| grid.get(position) == *state)
+                })
+                .count();
+            matching as f64 / painted as f64
+        })
+        .fold(0.0, f64::max)
+}
@@ -0,0 +1,190 @@
+use std::collections::VecDeque;
+
+// Longest period looked for, in steps
+pub const MAX_PERIOD: usize = 8192;
+// A period must repeat this many times in a row to be reported
+const REPEATS: usize = 3;
+// Shortest run of repeated steps accepted, short periods show up by chance in chaotic phases
+const MIN_WINDOW: usize = 1024;
+// Steps recorded between two searches
+const CHECK_INTERVAL: usize = 4096;
+
+/// Periodic behaviour of a single ant. A highway is a cycle with a non-zero displacement.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Cycle {
+    // Number of steps before the ant repeats itself
+    pub period: usize,
+    // Translation of the ant over one period, in cells of the lattice
+    pub displacement: (i32, i32),
+}
+
+impl Cycle {
+    pub fn is_highway(&self) -> bool {
+        self.displacement != (0, 0)
+    }
+}
+
+/// Records what a lone ant reads at every step and looks for a sequence repeating with a translation
+pub struct CycleDetector {
+    // Cell state read, ant state and heading packed together, oldest first
+    signatures: VecDeque<u32>,
+    positions: VecDeque<(i32, i32)>,
+    steps_since_check: usize,
+    cycle: Option<Cycle>,
+}
+
+impl CycleDetector {
+    pub fn new() -> Self {
+        CycleDetector {
+            signatures: VecDeque::new(),
+            positions: VecDeque::new(),
+            steps_since_check: 0,
+            cycle: None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.signatures.clear();
+        self.positions.clear();
+        self.steps_since_check = 0;
+        self.cycle = None;
+    }
+
+    // Record one step of the ant, with the cell it stands on before moving
+    pub fn record(&mut self, cell_state: u8, ant_state: u8, heading: u8, position: (i32, i32)) {
+        if self.signatures.len() == MAX_PERIOD * REPEATS {
+            self.signatures.pop_front();
+            self.positions.pop_front();
+        }
+        self.signatures
+            .push_back((cell_state as u32) << 16 | (ant_state as u32) << 8 | heading as u32);
+        self.positions.push_back(position);
+
+        self.steps_since_check += 1;
+        if self.steps_since_check >= CHECK_INTERVAL {
+            self.steps_since_check = 0;
+            self.cycle = self.detect();
+        }
+    }
+
+    // Move every recorded position, after the ant skipped whole periods of a highway
+    pub fn translate(&mut self, offset: (i32, i32)) {
+        for (x, y) in self.positions.iter_mut() {
+            *x += offset.0;
+            *y += offset.1;
+        }
+    }
+
+    // Account for steps a lone ant took along its highway without recording them, the latest
+    // period repeats shifted by the displacement
+    pub fn skip(&mut self, steps: u64) {
+        let Some(cycle) = self.cycle else {
+            return;
+        };
+        let (dx, dy) = cycle.displacement;
+        let periods = (steps / cycle.period as u64) as i64;
+        self.translate(((periods * dx as i64) as i32, (periods * dy as i64) as i32));
+        for _ in 0..steps % cycle.period as u64 {
+            let index = self.signatures.len() - cycle.period;
+            let signature = self.signatures[index];
+            let (x, y) = self.positions[index];
+            self.signatures.push_back(signature);
+            self.positions.push_back((x + dx, y + dy));
+            if self.signatures.len() > MAX_PERIOD * REPEATS {
+                self.signatures.pop_front();
+                self.positions.pop_front();
+            }
+        }
+    }
+
+    pub fn get_cycle(&self) -> Option<Cycle> {
+        self.cycle
+    }
+
+    // Smallest period over which the latest steps repeat with the same translation
+    fn detect(&self) -> Option<Cycle> {
+        let length = self.signatures.len();
+        (1..=MAX_PERIOD).find_map(|period| {
+            let window = (period * REPEATS).max(MIN_WINDOW);
+            if window > length {
+                return None;
+            }
+            let start = length - window;
+            let displacement = (
+                self.positions[length - 1].0 - self.positions[length - 1 - period].0,
+                self.positions[length - 1].1 - self.positions[length - 1 - period].1,
+            );
+            let repeats = (start + period..length).all(|index| {
+                let (x, y) = self.positions[index];
+                let (previous_x, previous_y) = self.positions[index - period];
+                self.signatures[index] == self.signatures[index - period]
+                    && (x - previous_x, y - previous_y) == displacement
+            });
+            repeats.then_some(Cycle {
+                period,
+                displacement,
+            })
+        })
+    }
+}
+
+impl Default for CycleDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamemodes::langton::{Ant, Direction, Gamestate};
+
+    #[test]
+    fn repeated_steps_with_a_translation_are_a_highway() {
+        let mut cycles = CycleDetector::new();
+        for step in 0..CHECK_INTERVAL as i32 * 2 {
+            let phase = step % 5;
+            cycles.record(
+                phase as u8 % 2,
+                0,
+                phase as u8 % 4,
+                (step / 5 * 3 + phase, -(step / 5)),
+            );
+        }
+        assert_eq!(
+            cycles.get_cycle(),
+            Some(Cycle {
+                period: 5,
+                displacement: (3, -1)
+            })
+        );
+    }
+
+    #[test]
+    fn steps_that_never_repeat_are_not_a_cycle() {
+        let mut cycles = CycleDetector::new();
+        let mut seed: u32 = 12345;
+        for step in 0..CHECK_INTERVAL as i32 * 2 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            cycles.record((seed >> 16) as u8 % 2, 0, (seed >> 20) as u8 % 4, (step, 0));
+        }
+        assert_eq!(cycles.get_cycle(), None);
+    }
+
+    #[test]
+    fn the_classic_ant_builds_a_highway() {
+        let mut gamestate = Gamestate::new();
+        gamestate.add_ant(Ant::place_ant(0, 0, Direction::Up));
+        gamestate.set_fast_forward(false);
+        gamestate.step(9000);
+        assert_eq!(gamestate.get_cycle(), None);
+        gamestate.step(6000);
+        let cycle = gamestate.get_cycle().unwrap();
+        assert_eq!(cycle.period, 104);
+        assert_eq!(
+            (cycle.displacement.0.abs(), cycle.displacement.1.abs()),
+            (2, 2)
+        );
+        assert!(cycle.is_highway());
+    }
+}
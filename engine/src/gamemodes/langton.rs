@@ -0,0 +1,2064 @@
+use crate::gamemodes::cycle::{Cycle, CycleDetector};
+use crate::gamemodes::turmite::{Transition, Turmite};
+use crate::gamemodes::{Gamemode, MAX_SPEED, MAX_UPDATE_WORK};
+use crate::grid::{CHUNK_AREA, ChunkedGrid, GridReader};
+use crate::highway::{Highway, HighwayStep};
+use crate::history::Change;
+use crate::world::{Edge, Landing, World};
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::collections::HashMap;
+use std::fmt;
+
+// Cell states are stored as u8, so a rule can have at most this many
+pub const MAX_RULE_STATES: usize = u8::MAX as usize;
+// Internal states of a turmite ant are also stored as u8, numbered from 0 to 255
+pub const MAX_TURMITE_STATES: usize = u8::MAX as usize + 1;
+
+// Fewer ants are stepped on the calling thread, splitting them would cost more than it saves
+pub const PARALLEL_MIN_ANTS: usize = 4096;
+// Ants stepped by one task of the thread pool
+const PARALLEL_CHUNK_ANTS: usize = 1024;
+
+// Chunks of the grid past which the simulation stops rather than exhausting the memory, 1 GiB
+pub const MAX_GRID_CHUNKS: usize = 1 << 18;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Direction {
+    // Convert the enum to a numerical value
+    pub fn as_index(&self) -> i8 {
+        match self {
+            Direction::Up => 0,
+            Direction::Right => 1,
+            Direction::Down => 2,
+            Direction::Left => 3,
+        }
+    }
+
+    // Convert a numerical value back to the enum
+    pub fn from_index(index: i8) -> Option<Self> {
+        match index {
+            0 => Some(Direction::Up),
+            1 => Some(Direction::Right),
+            2 => Some(Direction::Down),
+            3 => Some(Direction::Left),
+            _ => None,
+        }
+    }
+
+    // Rotate the direction by a given number of steps
+    pub fn rotate(&self, steps: isize) -> Self {
+        let index = self.as_index() as isize;
+        match (index + steps).rem_euclid(4) {
+            0 => Direction::Up,
+            1 => Direction::Right,
+            2 => Direction::Down,
+            _ => Direction::Left,
+        }
+    }
+
+    // Cycle the direction by a turn relative to the current heading
+    pub fn cycle_direction(&self, turn: &Turn) -> Direction {
+        self.rotate(turn.as_steps())
+    }
+}
+
+/// Heading of an ant on the hexagonal lattice, flat-topped cells use axial coordinates
+#[derive(Clone, PartialEq, Debug)]
+pub enum HexDirection {
+    North,
+    NorthEast,
+    SouthEast,
+    South,
+    SouthWest,
+    NorthWest,
+}
+
+impl HexDirection {
+    // Convert the enum to a numerical value, clockwise from North
+    pub fn as_index(&self) -> i8 {
+        match self {
+            HexDirection::North => 0,
+            HexDirection::NorthEast => 1,
+            HexDirection::SouthEast => 2,
+            HexDirection::South => 3,
+            HexDirection::SouthWest => 4,
+            HexDirection::NorthWest => 5,
+        }
+    }
+
+    // Convert a numerical value back to the enum
+    pub fn from_index(index: i8) -> Option<Self> {
+        match index {
+            0 => Some(HexDirection::North),
+            1 => Some(HexDirection::NorthEast),
+            2 => Some(HexDirection::SouthEast),
+            3 => Some(HexDirection::South),
+            4 => Some(HexDirection::SouthWest),
+            5 => Some(HexDirection::NorthWest),
+            _ => None,
+        }
+    }
+
+    // Rotate the direction by a given number of sixths of a turn
+    pub fn rotate(&self, steps: isize) -> Self {
+        let index = (self.as_index() as isize + steps).rem_euclid(6);
+        HexDirection::from_index(index as i8).unwrap_or(HexDirection::North)
+    }
+
+    // Axial offset (q, r) of the neighbouring cell in this direction
+    pub fn offset(&self) -> (i32, i32) {
+        match self {
+            HexDirection::North => (0, -1),
+            HexDirection::NorthEast => (1, -1),
+            HexDirection::SouthEast => (1, 0),
+            HexDirection::South => (0, 1),
+            HexDirection::SouthWest => (-1, 1),
+            HexDirection::NorthWest => (-1, 0),
+        }
+    }
+}
+
+/// Cell layout the ants of a rule walk on
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Lattice {
+    Square,
+    Hexagonal,
+}
+
+impl Lattice {
+    // Number of headings an ant can have
+    pub fn get_degree(&self) -> u8 {
+        match self {
+            Lattice::Square => 4,
+            Lattice::Hexagonal => 6,
+        }
+    }
+
+    // Position of the neighbouring cell for a heading index, None past the coordinates of the grid
+    pub fn advance(&self, position: (i32, i32), heading: u8) -> Option<(i32, i32)> {
+        let (x, y) = position;
+        let (offset_x, offset_y) = match self {
+            Lattice::Square => match heading {
+                0 => (0, -1),
+                1 => (1, 0),
+                2 => (0, 1),
+                _ => (-1, 0),
+            },
+            Lattice::Hexagonal => HexDirection::from_index(heading as i8)
+                .unwrap_or(HexDirection::North)
+                .offset(),
+        };
+        Some((x.checked_add(offset_x)?, y.checked_add(offset_y)?))
+    }
+
+    // Name of a heading index, such as "Up" or "NorthEast"
+    pub fn get_heading_name(&self, heading: u8) -> String {
+        match self {
+            Lattice::Square => format!("{:?}", Direction::Up.rotate(heading as isize)),
+            Lattice::Hexagonal => format!("{:?}", HexDirection::North.rotate(heading as isize)),
+        }
+    }
+}
+
+/// Turn made by an ant, relative to its heading
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Turn {
+    None,
+    Right,
+    UTurn,
+    Left,
+}
+
+impl Turn {
+    // Number of clockwise quarter turns
+    pub fn as_steps(&self) -> isize {
+        match self {
+            Turn::None => 0,
+            Turn::Right => 1,
+            Turn::UTurn => 2,
+            Turn::Left => 3,
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<Turn> {
+        match letter {
+            'N' => Some(Turn::None),
+            'R' => Some(Turn::Right),
+            'U' => Some(Turn::UTurn),
+            'L' => Some(Turn::Left),
+            _ => None,
+        }
+    }
+
+    pub fn as_letter(&self) -> char {
+        match self {
+            Turn::None => 'N',
+            Turn::Right => 'R',
+            Turn::UTurn => 'U',
+            Turn::Left => 'L',
+        }
+    }
+}
+
+/// Turn made by an ant on the hexagonal lattice, in sixths of a turn
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HexTurn {
+    None,
+    Right1,
+    Right2,
+    UTurn,
+    Left2,
+    Left1,
+}
+
+impl HexTurn {
+    // Number of clockwise sixths of a turn
+    pub fn as_steps(&self) -> isize {
+        match self {
+            HexTurn::None => 0,
+            HexTurn::Right1 => 1,
+            HexTurn::Right2 => 2,
+            HexTurn::UTurn => 3,
+            HexTurn::Left2 => 4,
+            HexTurn::Left1 => 5,
+        }
+    }
+
+    pub fn as_token(&self) -> &'static str {
+        match self {
+            HexTurn::None => "N",
+            HexTurn::Right1 => "R1",
+            HexTurn::Right2 => "R2",
+            HexTurn::UTurn => "U",
+            HexTurn::Left2 => "L2",
+            HexTurn::Left1 => "L1",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Ant {
+    pub x: i32,
+    pub y: i32,
+    // Index of a Direction or of a HexDirection, depending on the lattice of the rule
+    pub heading: u8,
+    // Internal state used by turmites, always 0 for Langton ants
+    pub state: u8,
+}
+
+impl Ant {
+    pub fn place_ant(x: i32, y: i32, direction: Direction) -> Ant {
+        Ant {
+            x,
+            y,
+            heading: direction.as_index() as u8,
+            state: 0,
+        }
+    }
+
+    pub fn place_hex_ant(x: i32, y: i32, direction: HexDirection) -> Ant {
+        Ant {
+            x,
+            y,
+            heading: direction.as_index() as u8,
+            state: 0,
+        }
+    }
+
+    // Heading on the square lattice
+    pub fn get_direction(&self) -> Direction {
+        Direction::Up.rotate(self.heading as isize)
+    }
+
+    // Heading on the hexagonal lattice
+    pub fn get_hex_direction(&self) -> HexDirection {
+        HexDirection::North.rotate(self.heading as isize)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleError {
+    Empty,
+    // Character that is not a known turn, with its 1-based position in the rule string
+    InvalidTurn {
+        character: char,
+        position: usize,
+    },
+    // Hexagonal turn letter without its 1 or 2 suffix, or a suffix on N or U
+    InvalidHexTurn {
+        token: String,
+        position: usize,
+    },
+    TooManyStates(usize),
+    // Turmite table with more ant states than MAX_TURMITE_STATES
+    TooManyAntStates(usize),
+    ColorCount {
+        states: usize,
+        colors: usize,
+    },
+    // Malformed turmite table, with the 1-based position of the problem
+    TableSyntax {
+        position: usize,
+        message: String,
+    },
+    // Turmite table entry that cannot be used, both indices start at 0
+    TableEntry {
+        state: usize,
+        color: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleError::Empty => write!(f, "the rule has no turns"),
+            RuleError::InvalidTurn {
+                character,
+                position,
+            } => write!(
+                f,
+                "invalid turn '{}' at position {} (expected R, L, N or U)",
+                character, position
+            ),
+            RuleError::InvalidHexTurn { token, position } => write!(
+                f,
+                "invalid hexagonal turn '{}' at position {} (expected L1, L2, N, U, R2 or R1)",
+                token, position
+            ),
+            RuleError::TooManyStates(states) => write!(
+                f,
+                "the rule has {} states but at most {} are supported",
+                states, MAX_RULE_STATES
+            ),
+            RuleError::TooManyAntStates(states) => write!(
+                f,
+                "the turmite has {} ant states but at most {} are supported",
+                states, MAX_TURMITE_STATES
+            ),
+            RuleError::ColorCount { states, colors } => {
+                write!(f, "{} colors given for {} states", colors, states)
+            }
+            RuleError::TableSyntax { position, message } => {
+                write!(
+                    f,
+                    "{} at position {} of the turmite table",
+                    message, position
+                )
+            }
+            RuleError::TableEntry {
+                state,
+                color,
+                message,
+            } => write!(
+                f,
+                "turmite entry for state {} and color {}: {}",
+                state, color, message
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+/// Reason why the simulation stopped before the number of iterations asked for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepLimit {
+    // An ant, or the trail of a highway, would leave the coordinates of the grid
+    Edge,
+    // The grid holds MAX_GRID_CHUNKS chunks
+    Memory,
+}
+
+impl fmt::Display for StepLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepLimit::Edge => write!(f, "an ant reached the edge of the grid"),
+            StepLimit::Memory => write!(
+                f,
+                "the grid reached its memory limit of {} MiB",
+                (MAX_GRID_CHUNKS * CHUNK_AREA) >> 20
+            ),
+        }
+    }
+}
+
+/// Reason why the simulation cannot go back by one iteration
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReverseError {
+    // Two transitions of the rule write the same color and go to the same state
+    IrreversibleRule,
+    // The iteration counter is already at 0
+    Start,
+    // Ants of a turmite left the same cell, the first of them decided the color written
+    SharedCell((i32, i32)),
+    // A cell an ant came from holds a state the rule does not have
+    UnknownState((i32, i32)),
+    // Ants may have turned around against a wall or left the world, only wrapping edges reverse
+    Edge(Edge),
+    // The cell behind an ant is past the coordinates of the grid
+    GridEdge((i32, i32)),
+}
+
+impl fmt::Display for ReverseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReverseError::IrreversibleRule => {
+                write!(f, "the rule cannot tell which transition led to a cell")
+            }
+            ReverseError::Start => write!(f, "the simulation is back at its first iteration"),
+            ReverseError::SharedCell((x, y)) => write!(
+                f,
+                "several turmites left cell ({}, {}) at once, their previous states are unknown",
+                x, y
+            ),
+            ReverseError::UnknownState((x, y)) => write!(
+                f,
+                "cell ({}, {}) holds a state the rule does not have",
+                x, y
+            ),
+            ReverseError::Edge(edge) => write!(
+                f,
+                "ants cannot be followed back through the {} edges of the world",
+                edge.get_name()
+            ),
+            ReverseError::GridEdge((x, y)) => write!(
+                f,
+                "the ant on cell ({}, {}) cannot come from past the edge of the grid",
+                x, y
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReverseError {}
+
+/// A rule is either a string of turn letters ("RLNU") for Langton ants,
+/// a string of hexagonal turns ("L2NNL1L2L1") for hexagonal ants,
+/// or a turmite table ("{{{1,2,0},{0,8,0}}}"). All run through a transition table.
+#[derive(Clone)]
+pub struct Rule {
+    name: String,
+    description: String,
+    notation: String,
+    lattice: Lattice,
+    table: Turmite,
+    // Colors are stored as 0xRRGGBB so the engine stays independent of any renderer
+    colors: Vec<u32>,
+}
+
+impl Rule {
+    // Constructor method as an associated function
+    pub fn new(name: &str, notation: &str, colors: (u32, u32)) -> Result<Self, RuleError> {
+        let (notation, lattice, table) = Rule::convert_notation(notation)?;
+        let number_of_steps = table.get_colors();
+        Ok(Rule {
+            name: name.to_string(),
+            description: String::new(),
+            notation,
+            lattice,
+            table,
+            colors: Rule::generate_gradient(colors.0, colors.1, number_of_steps),
+        })
+    }
+
+    // Constructor with one explicit color per state instead of a gradient
+    pub fn with_colors(name: &str, notation: &str, colors: Vec<u32>) -> Result<Self, RuleError> {
+        let (notation, lattice, table) = Rule::convert_notation(notation)?;
+        if colors.len() != table.get_colors() {
+            return Err(RuleError::ColorCount {
+                states: table.get_colors(),
+                colors: colors.len(),
+            });
+        }
+        Ok(Rule {
+            name: name.to_string(),
+            description: String::new(),
+            notation,
+            lattice,
+            table,
+            colors,
+        })
+    }
+
+    // Build the transition table of any notation, tables start with a brace
+    // and hexagonal rules are the only ones with digits
+    fn convert_notation(notation: &str) -> Result<(String, Lattice, Turmite), RuleError> {
+        let notation = notation.trim();
+        if notation.starts_with('{') {
+            let table = Turmite::parse(notation)?;
+            Ok((table.get_notation(), Lattice::Square, table))
+        } else if notation.chars().any(|c| c.is_ascii_digit()) {
+            let turns = Rule::convert_hex_turns(notation)?;
+            Ok((
+                notation.to_string(),
+                Lattice::Hexagonal,
+                Turmite::from_hex_turns(&turns),
+            ))
+        } else {
+            let turns = Rule::convert_turns(notation)?;
+            Ok((
+                notation.to_string(),
+                Lattice::Square,
+                Turmite::from_turns(&turns),
+            ))
+        }
+    }
+
+    // Convert a string of hexagonal turns such as "L2NNL1L2L1" to a vector of HexTurn
+    fn convert_hex_turns(turns: &str) -> Result<Vec<HexTurn>, RuleError> {
+        let characters: Vec<char> = turns.chars().collect();
+        let mut result = Vec::new();
+        let mut index = 0;
+        while index < characters.len() {
+            let letter = characters[index];
+            let digit = characters.get(index + 1).filter(|c| c.is_ascii_digit());
+            let invalid = |length: usize| RuleError::InvalidHexTurn {
+                token: characters[index..index + length].iter().collect(),
+                position: index + 1,
+            };
+            let turn = match (letter, digit) {
+                ('N', None) => HexTurn::None,
+                ('U', None) => HexTurn::UTurn,
+                ('R', Some('1')) => HexTurn::Right1,
+                ('R', Some('2')) => HexTurn::Right2,
+                ('L', Some('1')) => HexTurn::Left1,
+                ('L', Some('2')) => HexTurn::Left2,
+                (_, Some(_)) => return Err(invalid(2)),
+                ('R' | 'L', None) => return Err(invalid(1)),
+                _ => {
+                    return Err(RuleError::InvalidTurn {
+                        character: letter,
+                        position: index + 1,
+                    });
+                }
+            };
+            result.push(turn);
+            index += if digit.is_some() { 2 } else { 1 };
+        }
+
+        match result.len() {
+            0 => Err(RuleError::Empty),
+            length if length > MAX_RULE_STATES => Err(RuleError::TooManyStates(length)),
+            _ => Ok(result),
+        }
+    }
+
+    // Convert a string of turn letters to a vector of Turn
+    fn convert_turns(turns: &str) -> Result<Vec<Turn>, RuleError> {
+        let turns = turns
+            .chars()
+            .enumerate()
+            .map(|(index, c)| {
+                Turn::from_letter(c).ok_or(RuleError::InvalidTurn {
+                    character: c,
+                    position: index + 1,
+                })
+            })
+            .collect::<Result<Vec<Turn>, RuleError>>()?;
+
+        match turns.len() {
+            0 => Err(RuleError::Empty),
+            length if length > MAX_RULE_STATES => Err(RuleError::TooManyStates(length)),
+            _ => Ok(turns),
+        }
+    }
+
+    // Generate gradient function
+    fn generate_gradient(start: u32, end: u32, steps: usize) -> Vec<u32> {
+        let start_r = ((start >> 16) & 0xFF) as f32;
+        let start_g = ((start >> 8) & 0xFF) as f32;
+        let start_b = (start & 0xFF) as f32;
+
+        let end_r = ((end >> 16) & 0xFF) as f32;
+        let end_g = ((end >> 8) & 0xFF) as f32;
+        let end_b = (end & 0xFF) as f32;
+
+        (0..steps)
+            .map(|i| {
+                // A single state takes the start color
+                let t = if steps > 1 {
+                    i as f32 / (steps as f32 - 1.0)
+                } else {
+                    0.0
+                };
+                let r = ((1.0 - t) * start_r + t * end_r) as u8;
+                let g = ((1.0 - t) * start_g + t * end_g) as u8;
+                let b = ((1.0 - t) * start_b + t * end_b) as u8;
+
+                ((r as u32) << 16) | ((g as u32) << 8) | b as u32
+            })
+            .collect()
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn set_description(&mut self, description: &str) {
+        self.description = description.to_string();
+    }
+
+    pub fn get_transition(&self, ant_state: u8, cell_state: u8) -> &Transition {
+        self.table.get_transition(ant_state, cell_state)
+    }
+
+    pub fn get_table(&self) -> &Turmite {
+        &self.table
+    }
+
+    pub fn get_lattice(&self) -> Lattice {
+        self.lattice
+    }
+
+    // Whether the ants of this rule carry an internal state
+    pub fn is_turmite(&self) -> bool {
+        self.notation.starts_with('{')
+    }
+
+    pub fn get_rule_color(&self, position: usize) -> u32 {
+        self.colors[position]
+    }
+
+    // Turn letters or turmite table the rule was created from
+    pub fn get_notation(&self) -> &str {
+        &self.notation
+    }
+
+    // Number of cell states (colors)
+    pub fn get_length(&self) -> usize {
+        self.table.get_colors()
+    }
+
+    pub fn get_colors(&self) -> &[u32] {
+        &self.colors
+    }
+}
+
+pub struct Gamestate {
+    grid: ChunkedGrid,
+    // Ants in the order they were placed, the earliest of several ants on a cell writes its color
+    ants: Vec<Ant>,
+    // Buffers reused from one iteration to the next so that stepping never allocates:
+    // the cell and color written for every ant, the ants of the previous iteration,
+    // and the first ant of every cell ants stepped from
+    writes: Vec<((i32, i32), u8)>,
+    previous_ants: Vec<Ant>,
+    occupancy: HashMap<(i32, i32), usize>,
+    paused: bool,
+    // Runs the simulation backward, one iteration at a time
+    reversed: bool,
+    // Why the reversed simulation last stopped, kept until the frontend takes it
+    reverse_error: Option<ReverseError>,
+    // Why the last forward run stopped early, kept until the frontend takes it
+    step_limit: Option<StepLimit>,
+    iteration: u128,
+    speed: u64,
+    cursor_size: (u8, u8),
+    max_cursor_size: u8,
+    // Heading of the ants placed with the brush, reduced to the lattice of the rule when used
+    placement_heading: u8,
+    selected_rule: usize,
+    rules: Vec<Rule>,
+    // Only follows the ant while there is exactly one
+    cycles: CycleDetector,
+    // Skips whole periods once the cycle detector finds a highway
+    fast_forward: bool,
+    // Rectangle holding the ants and the painted cells, the grid is unbounded without it
+    world: Option<World>,
+    // What the gamestate does while the undo history listens
+    journal: Option<Change>,
+    // Threads stepping large ant populations, 0 for one per core and 1 to stay sequential.
+    // Falls back to 1 when the pool cannot use more than one thread.
+    threads: usize,
+    // Built on the first step with enough ants
+    pool: Option<ThreadPool>,
+}
+
+impl Gamestate {
+    pub fn new() -> Gamestate {
+        Gamestate {
+            grid: ChunkedGrid::new(),
+            ants: Vec::new(),
+            writes: Vec::new(),
+            previous_ants: Vec::new(),
+            occupancy: HashMap::new(),
+            paused: true,
+            reversed: false,
+            reverse_error: None,
+            step_limit: None,
+            iteration: 0,
+            speed: 1,
+            cursor_size: (1, 1),
+            max_cursor_size: 10,
+            placement_heading: 0,
+            selected_rule: 0,
+            rules: Gamestate::builtin_rules(),
+            cycles: CycleDetector::new(),
+            fast_forward: true,
+            world: None,
+            journal: None,
+            threads: 0,
+            pool: None,
+        }
+    }
+
+    fn builtin_rules() -> Vec<Rule> {
+        [
+            ("Classic", "RL", (0x00000, 0xAAAAAA)),
+            ("Lettuce", "LRL", (0x005524, 0x2bb25a)),
+            ("Amethyst Cube", "RLLLLLRRL", (0x260511, 0x95097e)),
+            ("Saphyre Triangle", "RRLLLRLLLRRR", (0x000021, 0x06d7b4)),
+            ("Brain", "RRLL", (0x120021, 0xFF00AA)),
+            ("Yellow Highway", "LLRRRLRLRLLR", (0x333300, 0xFFFF00)),
+            ("Cubic Crystal", "RLLR", (0x00AAAA, 0xFF5500)),
+            ("Mini Brain", "RRLLRR", (0xDAF7A6, 0x581845)),
+            ("Pollen", "LRRLRL", (0xFFC300, 0xFF5733)),
+            ("Ocean", "RRRLLLL", (0x11998E, 0x3B5998)),
+            ("Cubic Crystal II", "RLLLRRR", (0x00FFFF, 0xFF00FF)),
+        ]
+        .iter()
+        // Built-in turn strings are constants, none of them can be rejected
+        .filter_map(|(name, turns, colors)| Rule::new(name, turns, *colors).ok())
+        .collect()
+    }
+
+    pub fn get_grid(&self) -> &ChunkedGrid {
+        &self.grid
+    }
+
+    pub fn get_grid_value(&self, key: (i32, i32)) -> u8 {
+        self.grid.get(key)
+    }
+
+    // Cells outside the world stay blank
+    pub fn set_grid_value(&mut self, key: (i32, i32), value: u8) {
+        if self.world.is_some_and(|world| !world.contains(key)) {
+            return;
+        }
+        write_cell(&mut self.grid, &mut self.journal, key, value);
+    }
+
+    pub fn get_painted_cells(&self) -> usize {
+        self.grid.len()
+    }
+
+    // Smallest rectangle (min_x, min_y, max_x, max_y) containing every painted cell
+    pub fn get_bounding_box(&self) -> Option<(i32, i32, i32, i32)> {
+        self.grid.bounding_box()
+    }
+
+    // Number of painted cells in each state of the selected rule
+    pub fn get_state_counts(&self) -> Vec<usize> {
+        self.grid.state_counts(self.get_rule_length() as usize)
+    }
+
+    pub fn clear_grid(&mut self) {
+        if let Some(journal) = &mut self.journal {
+            for (position, state) in self.grid.iter() {
+                journal.record_cell(position, state, 0);
+            }
+        }
+        self.grid.clear();
+        self.cycles.clear();
+    }
+
+    pub fn clear_ants(&mut self) {
+        if let Some(journal) = &mut self.journal {
+            journal.record_removed_ants(self.ants.iter().cloned().enumerate().collect());
+        }
+        self.ants.clear();
+        self.cycles.clear();
+    }
+
+    // Place an ant facing the placement heading on every cell of the brush
+    pub fn add_ants(&mut self, position: (i32, i32)) {
+        let heading = self.get_placement_heading();
+        for x in 0..self.cursor_size.0 {
+            for y in 0..self.cursor_size.1 {
+                self.add_ant(Ant {
+                    x: position.0 + x as i32,
+                    y: position.1 + y as i32,
+                    heading,
+                    state: 0,
+                });
+            }
+        }
+    }
+
+    // Remove every ant on the cells of the brush, returning how many were removed
+    pub fn remove_ants(&mut self, position: (i32, i32)) -> usize {
+        let (width, height) = self.cursor_size;
+        let removed = self.remove_ants_where(|ant| {
+            (0..width as i32).contains(&(ant.x - position.0))
+                && (0..height as i32).contains(&(ant.y - position.1))
+        });
+        if removed > 0 {
+            self.cycles.clear();
+        }
+        removed
+    }
+
+    // Remove the ants matching a condition, journaling each of them with its index
+    fn remove_ants_where(&mut self, condition: impl Fn(&Ant) -> bool) -> usize {
+        let journaling = self.journal.is_some();
+        let mut removed = Vec::new();
+        let mut index = 0;
+        self.ants.retain(|ant| {
+            let matched = condition(ant);
+            if matched && journaling {
+                removed.push((index, ant.clone()));
+            }
+            index += 1;
+            !matched
+        });
+        let count = index - self.ants.len();
+        if let Some(journal) = &mut self.journal {
+            journal.record_removed_ants(removed);
+        }
+        count
+    }
+
+    pub fn get_placement_heading(&self) -> u8 {
+        self.placement_heading % self.rules[self.selected_rule].get_lattice().get_degree()
+    }
+
+    pub fn set_placement_heading(&mut self, heading: u8) {
+        self.placement_heading = heading
+    }
+
+    // Turn the placement heading clockwise by a number of steps of the lattice
+    pub fn rotate_placement_heading(&mut self, steps: isize) {
+        let degree = self.rules[self.selected_rule].get_lattice().get_degree() as isize;
+        self.placement_heading =
+            (self.get_placement_heading() as isize + steps).rem_euclid(degree) as u8;
+    }
+
+    // Ants outside the world are not placed
+    pub fn add_ant(&mut self, ant: Ant) {
+        if self
+            .world
+            .is_some_and(|world| !world.contains((ant.x, ant.y)))
+        {
+            return;
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.record_added_ants(std::slice::from_ref(&ant));
+        }
+        self.ants.push(ant);
+        self.cycles.clear();
+    }
+
+    pub fn get_ants(&self) -> impl Iterator<Item = &Ant> {
+        self.ants.iter()
+    }
+
+    pub fn get_ants_in_region(&self, min_x: i32, max_x: i32, min_y: i32, max_y: i32) -> Vec<&Ant> {
+        self.ants
+            .iter()
+            .filter(|ant| ant.x >= min_x && ant.x <= max_x && ant.y >= min_y && ant.y <= max_y)
+            .collect()
+    }
+
+    pub fn get_total_ants(&self) -> usize {
+        self.ants.len()
+    }
+
+    pub fn get_total_visible_ants(&self, visible_range: (i32, i32, i32, i32)) -> usize {
+        self.get_ants_in_region(
+            visible_range.0,
+            visible_range.2,
+            visible_range.1,
+            visible_range.3,
+        )
+        .len()
+    }
+
+    pub fn reset(&mut self) {
+        self.clear_grid();
+        self.clear_ants();
+        self.set_iteration(0);
+    }
+
+    // Start recording every change into a journal for the undo history
+    pub fn open_journal(&mut self) {
+        self.journal = Some(Change::default());
+    }
+
+    // Stop recording, returning the changes made since the journal was opened
+    pub fn close_journal(&mut self) -> Option<Change> {
+        self.journal.take().filter(|change| !change.is_empty())
+    }
+
+    // Bring the gamestate back to its state before a change. The change is replayed against
+    // the current state, which differs from the state after it when a simulation run that was
+    // not journaled came in between: only the recorded cells, ants, rule and world go back and
+    // the iterations run since are kept.
+    pub fn undo_change(&mut self, change: &Change) {
+        for (position, (before, _)) in &change.cells {
+            self.grid.set(*position, *before);
+        }
+        change.undo_ants(&mut self.ants);
+        if let Some((before, _)) = change.rule {
+            self.selected_rule = before;
+        }
+        if let Some((before, after)) = change.iteration {
+            self.iteration = self.iteration.saturating_sub(after).saturating_add(before);
+        }
+        if let Some((before, _)) = change.world {
+            self.world = before;
+            self.clip_to_world();
+        }
+        self.cycles.clear();
+    }
+
+    // Apply a change again after undoing it
+    pub fn redo_change(&mut self, change: &Change) {
+        for (position, (_, after)) in &change.cells {
+            self.grid.set(*position, *after);
+        }
+        change.redo_ants(&mut self.ants);
+        if let Some((_, after)) = change.rule {
+            self.selected_rule = after;
+        }
+        if let Some((before, after)) = change.iteration {
+            self.iteration = self.iteration.saturating_sub(before).saturating_add(after);
+        }
+        if let Some((_, after)) = change.world {
+            self.world = after;
+            self.clip_to_world();
+        }
+        self.cycles.clear();
+    }
+
+    pub fn get_world(&self) -> Option<World> {
+        self.world
+    }
+
+    // Keep the ants in a world, or let them roam the unbounded grid with None.
+    // The ants and the painted cells outside the new world are removed.
+    pub fn set_world(&mut self, world: Option<World>) {
+        if let Some(journal) = &mut self.journal {
+            journal.record_world(self.world, world);
+        }
+        self.world = world;
+        self.clip_to_world();
+        self.cycles.clear();
+    }
+
+    // Remove the ants and the painted cells outside the world
+    fn clip_to_world(&mut self) {
+        if let Some(world) = self.world {
+            if let Some(journal) = &mut self.journal {
+                for (position, state) in self.grid.iter() {
+                    if !world.contains(position) {
+                        journal.record_cell(position, state, 0);
+                    }
+                }
+            }
+            self.grid.clip(world.get_bounds());
+            self.remove_ants_where(|ant| !world.contains((ant.x, ant.y)));
+        }
+    }
+
+    // Period and displacement of the lone ant once it repeats itself
+    pub fn get_cycle(&self) -> Option<Cycle> {
+        self.cycles.get_cycle()
+    }
+
+    pub fn get_pause_state(&self) -> bool {
+        self.paused
+    }
+
+    pub fn invert_pause_state(&mut self) {
+        self.paused = !self.paused
+    }
+
+    pub fn set_pause_state(&mut self, value: bool) {
+        self.paused = value;
+    }
+
+    pub fn get_reverse_state(&self) -> bool {
+        self.reversed
+    }
+
+    pub fn set_reverse_state(&mut self, value: bool) {
+        self.reversed = value;
+    }
+
+    // Reason why the last call to step or update stopped before the end, reported once
+    pub fn take_step_limit(&mut self) -> Option<StepLimit> {
+        self.step_limit.take()
+    }
+
+    // Reason why update stopped running the simulation backward, reported once
+    pub fn take_reverse_error(&mut self) -> Option<ReverseError> {
+        self.reverse_error.take()
+    }
+
+    pub fn get_iteration(&self) -> &u128 {
+        &self.iteration
+    }
+
+    pub fn set_iteration(&mut self, value: u128) {
+        if let Some(journal) = &mut self.journal {
+            journal.record_iteration(self.iteration, value);
+        }
+        self.iteration = value
+    }
+
+    pub fn increment_iteration(&mut self, value: u64) {
+        self.iteration += value as u128
+    }
+
+    pub fn get_speed(&self) -> u64 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, value: u64) {
+        self.speed = value.min(MAX_SPEED)
+    }
+
+    pub fn get_fast_forward(&self) -> bool {
+        self.fast_forward
+    }
+
+    pub fn set_fast_forward(&mut self, value: bool) {
+        self.fast_forward = value
+    }
+
+    pub fn get_thread_count(&self) -> usize {
+        self.threads
+    }
+
+    pub fn set_thread_count(&mut self, threads: usize) {
+        if threads != self.threads {
+            self.threads = threads;
+            self.pool = None;
+        }
+    }
+
+    pub fn get_cursor_dimensions(&self) -> (u8, u8) {
+        self.cursor_size
+    }
+
+    pub fn set_cursor_dimensions(&mut self, dimensions: (u8, u8)) {
+        self.cursor_size = (
+            dimensions.0.min(self.max_cursor_size),
+            dimensions.1.min(self.max_cursor_size),
+        );
+    }
+
+    pub fn get_cursor_x(&self) -> u8 {
+        self.cursor_size.0
+    }
+
+    pub fn get_cursor_y(&self) -> u8 {
+        self.cursor_size.1
+    }
+
+    pub fn increment_cursor_x(&mut self, value: u8) {
+        if self.cursor_size.0 + value < self.max_cursor_size {
+            self.cursor_size.0 += value;
+        } else {
+            self.cursor_size.0 = self.max_cursor_size;
+        }
+    }
+
+    pub fn increment_cursor_y(&mut self, value: u8) {
+        if self.cursor_size.1 + value < self.max_cursor_size {
+            self.cursor_size.1 += value;
+        } else {
+            self.cursor_size.1 = self.max_cursor_size;
+        }
+    }
+
+    pub fn decrement_cursor_x(&mut self, value: u8) {
+        match self.cursor_size.0.checked_sub(value) {
+            Some(result) => self.cursor_size.0 = result,
+            None => self.cursor_size.0 = 0,
+        }
+    }
+
+    pub fn decrement_cursor_y(&mut self, value: u8) {
+        match self.cursor_size.1.checked_sub(value) {
+            Some(result) => self.cursor_size.1 = result,
+            None => self.cursor_size.1 = 0,
+        }
+    }
+
+    // Indices past the last rule are ignored
+    pub fn select_rule(&mut self, rule_number: usize) {
+        if rule_number < self.rules.len() {
+            if let Some(journal) = &mut self.journal {
+                journal.record_rule(self.selected_rule, rule_number);
+            }
+            self.selected_rule = rule_number
+        }
+    }
+
+    // Append a rule to the list and return its index
+    pub fn add_rule(&mut self, rule: Rule) -> usize {
+        self.rules.push(rule);
+        self.rules.len() - 1
+    }
+
+    // Replace the rule with the same name, or append it when the name is new
+    pub fn merge_rule(&mut self, rule: Rule) -> usize {
+        match self
+            .rules
+            .iter()
+            .position(|existing| existing.get_name() == rule.get_name())
+        {
+            Some(index) => {
+                self.rules[index] = rule;
+                index
+            }
+            None => self.add_rule(rule),
+        }
+    }
+
+    pub fn get_rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn get_selected_rule(&self) -> usize {
+        self.selected_rule
+    }
+
+    pub fn get_rule(&self) -> &Rule {
+        &self.rules[self.selected_rule]
+    }
+
+    pub fn get_rule_length(&self) -> u8 {
+        self.rules[self.selected_rule].get_length() as u8
+    }
+
+    // Advance the simulation unless it is paused, returning the number of iterations performed.
+    // A reversed simulation goes backward instead, and keeps the reason it stopped for
+    // take_reverse_error. Either way it pauses once it cannot go further. At most
+    // MAX_UPDATE_WORK cells are visited, fewer iterations are performed at high speeds.
+    pub fn update(&mut self, number_of_iterations: u64) -> u64 {
+        if self.paused {
+            return 0;
+        }
+        let (performed, stopped) = if self.reversed {
+            let (performed, error) = self.reverse(number_of_iterations, MAX_UPDATE_WORK);
+            self.reverse_error = error;
+            (performed, self.reverse_error.is_some())
+        } else {
+            let performed = self.advance(number_of_iterations, MAX_UPDATE_WORK);
+            (performed, self.step_limit.is_some())
+        };
+        if stopped {
+            self.paused = true;
+        }
+        performed
+    }
+
+    // Advance the simulation regardless of the pause state, returning the number of iterations
+    // performed. A lone ant on a highway of the unbounded grid skips whole periods at once. The
+    // simulation stops before an ant would leave the coordinates of the grid, the reason it
+    // stopped early is given by take_step_limit.
+    pub fn step(&mut self, number_of_iterations: u64) -> u64 {
+        self.advance(number_of_iterations, u64::MAX)
+    }
+
+    // Step until the number of iterations is reached, or until the cells visited exceed the
+    // work allowed. At least one iteration is always performed.
+    fn advance(&mut self, number_of_iterations: u64, work: u64) -> u64 {
+        self.step_limit = None;
+        if number_of_iterations == 0 {
+            return 0;
+        }
+        let mut single_ant = self.ants.len() == 1;
+        if !single_ant {
+            self.cycles.clear();
+        }
+        // Every ant moves, the journal replaces all of them
+        let iteration = self.iteration;
+        if let Some(journal) = &mut self.journal {
+            journal.record_removed_ants(self.ants.iter().cloned().enumerate().collect());
+        }
+
+        // A pool that cannot be built, or that only gets one thread, leaves the gamestate sequential
+        let parallel = self.threads != 1 && self.ants.len() >= PARALLEL_MIN_ANTS;
+        if parallel && self.pool.is_none() {
+            self.pool = ThreadPoolBuilder::new()
+                .num_threads(self.threads)
+                .build()
+                .ok()
+                .filter(|pool| pool.current_num_threads() > 1);
+            if self.pool.is_none() {
+                self.threads = 1;
+            }
+        }
+
+        let Gamestate {
+            grid,
+            ants,
+            writes,
+            rules,
+            selected_rule,
+            cycles,
+            fast_forward,
+            world,
+            journal,
+            pool,
+            step_limit,
+            ..
+        } = self;
+        let rule = &rules[*selected_rule];
+        let lattice = rule.get_lattice();
+        let world = *world;
+        let mut visited = 0;
+        // Ants leaving a world with killing edges are removed once the iteration is over
+        let killing = world.filter(|world| world.get_edge() == Edge::Kill);
+
+        let mut remaining = number_of_iterations;
+        while remaining > 0 {
+            // Nothing changes once every ant is gone
+            if ants.is_empty() {
+                remaining = 0;
+                break;
+            }
+            if remaining < number_of_iterations && visited >= work {
+                break;
+            }
+            if grid.get_chunk_count() >= MAX_GRID_CHUNKS {
+                *step_limit = Some(StepLimit::Memory);
+                break;
+            }
+            // The iteration is not started when an ant would step out of the coordinates
+            if ants.iter().any(|ant| leaves_grid(grid, rule, ant)) {
+                *step_limit = Some(StepLimit::Edge);
+                break;
+            }
+
+            // A lone ant cannot share its cell, it writes as soon as it reads
+            if single_ant {
+                let ant = &mut ants[0];
+                if *fast_forward
+                    && world.is_none()
+                    && let Some(cycle) = cycles.get_cycle()
+                    && cycle.is_highway()
+                    && remaining >= cycle.period as u64
+                {
+                    // The grid keeps the trail of an unjournaled ant as a highway it extends
+                    if journal.is_none() {
+                        let (grown, checked) = follow_highway(
+                            grid,
+                            cycles,
+                            ant,
+                            remaining,
+                            work.saturating_sub(visited),
+                        );
+                        visited += checked;
+                        if grown > 0 {
+                            remaining -= grown;
+                            continue;
+                        }
+                    }
+                    // Close to the edge of the grid the ant goes on one step at a time
+                    if remaining / cycle.period as u64 >= 2 && highway_room(ant, cycle) > 0 {
+                        let work = work.saturating_sub(visited);
+                        let (performed, cost) =
+                            skip_highway(grid, journal, cycles, rule, ant, cycle, remaining, work);
+                        remaining -= performed;
+                        visited += cost;
+                        continue;
+                    }
+                }
+                let position = (ant.x, ant.y);
+                let current_state = grid.get(position);
+                cycles.record(current_state, ant.state, ant.heading, position);
+                let transition = rule.get_transition(ant.state, current_state);
+                write_cell(grid, journal, position, transition.write);
+                move_ant(ant, transition, lattice, world);
+                remaining -= 1;
+                visited += 1;
+                if killing.is_some_and(|world| !world.contains((ant.x, ant.y))) {
+                    ants.clear();
+                    cycles.clear();
+                    single_ant = false;
+                }
+                continue;
+            }
+
+            // Every ant reads its cell before any of them writes
+            writes.clear();
+            match pool {
+                Some(pool) if parallel => {
+                    move_ants_parallel(pool, grid.reader(), ants, writes, rule, world);
+                }
+                _ => {
+                    for ant in ants.iter_mut() {
+                        let position = (ant.x, ant.y);
+                        let transition = rule.get_transition(ant.state, grid.get(position));
+                        writes.push((position, transition.write));
+                        move_ant(ant, transition, lattice, world);
+                    }
+                }
+            }
+
+            // Written from the last ant to the first, so that the earliest ant of a cell wins
+            for (position, state) in writes.iter().rev() {
+                write_cell(grid, journal, *position, *state);
+            }
+            if let Some(world) = killing {
+                ants.retain(|ant| world.contains((ant.x, ant.y)));
+            }
+            remaining -= 1;
+            visited += writes.len() as u64;
+        }
+
+        let performed = number_of_iterations - remaining;
+        self.increment_iteration(performed);
+        if let Some(journal) = &mut self.journal {
+            journal.record_added_ants(&self.ants);
+            journal.record_iteration(iteration, self.iteration);
+        }
+        performed
+    }
+
+    // Whether the simulation can go back by one iteration
+    pub fn check_step_back(&self) -> Result<(), ReverseError> {
+        if self.rules[self.selected_rule]
+            .get_table()
+            .get_inverse()
+            .is_none()
+        {
+            return Err(ReverseError::IrreversibleRule);
+        }
+        if let Some(world) = self.world
+            && world.get_edge() != Edge::Wrap
+        {
+            return Err(ReverseError::Edge(world.get_edge()));
+        }
+        if self.iteration == 0 {
+            return Err(ReverseError::Start);
+        }
+        Ok(())
+    }
+
+    // Run the simulation backward without any stored history: an ant came from the cell behind
+    // it, and that cell now holds the color its transition wrote, which the inverse of the rule
+    // turns back into the color and state it read. Stops at iteration 0, returning the number
+    // of iterations undone, or an error when not even one could be.
+    pub fn step_back(&mut self, number_of_iterations: u64) -> Result<u64, ReverseError> {
+        match self.reverse(number_of_iterations, u64::MAX) {
+            (0, Some(error)) => Err(error),
+            (performed, _) => Ok(performed),
+        }
+    }
+
+    // Step back within the time of a frame: at most MAX_UPDATE_WORK ants are moved, fewer
+    // iterations are undone at high speeds
+    pub fn step_back_frame(&mut self, number_of_iterations: u64) -> Result<u64, ReverseError> {
+        match self.reverse(number_of_iterations, MAX_UPDATE_WORK) {
+            (0, Some(error)) => Err(error),
+            (performed, _) => Ok(performed),
+        }
+    }
+
+    // Go back by up to a number of iterations, or until the ants moved exceed the work allowed,
+    // returning how many were undone and, when it stopped before iteration 0 could be passed,
+    // the reason why. At least one iteration is always attempted.
+    fn reverse(&mut self, number_of_iterations: u64, work: u64) -> (u64, Option<ReverseError>) {
+        if let Err(error) = self.check_step_back() {
+            return (0, Some(error));
+        }
+        let Gamestate {
+            grid,
+            ants,
+            writes,
+            previous_ants,
+            occupancy,
+            rules,
+            selected_rule,
+            world,
+            journal,
+            iteration,
+            ..
+        } = self;
+        let rule = &rules[*selected_rule];
+        let Some(inverse) = rule.get_table().get_inverse() else {
+            return (0, Some(ReverseError::IrreversibleRule));
+        };
+        let lattice = rule.get_lattice();
+        let degree = lattice.get_degree();
+        let colors = rule.get_length();
+        let turmite = rule.get_table().get_states() > 1;
+        let before = *iteration;
+        // Every ant moves, the journal replaces all of them unless none could
+        let replaced = journal.is_some().then(|| ants.clone());
+
+        let mut performed = 0;
+        let mut visited = 0;
+        let mut error = None;
+        'iterations: while performed < number_of_iterations && *iteration > 0 {
+            if performed > 0 && visited >= work {
+                break;
+            }
+            visited += ants.len() as u64;
+            // Nothing is written until the whole iteration is known to be reversible
+            writes.clear();
+            previous_ants.clear();
+            occupancy.clear();
+            for (index, ant) in ants.iter().enumerate() {
+                let Some(behind) = cell_behind(ant, lattice, *world) else {
+                    error = Some(ReverseError::GridEdge((ant.x, ant.y)));
+                    break 'iterations;
+                };
+                // Every ant of a turmite carries its own state but only the first one wrote
+                if turmite && occupancy.insert(behind, index).is_some() {
+                    error = Some(ReverseError::SharedCell(behind));
+                    break 'iterations;
+                }
+                let written = grid.get(behind);
+                if written as usize >= colors {
+                    error = Some(ReverseError::UnknownState(behind));
+                    break 'iterations;
+                }
+                let (state, color) = inverse[ant.state as usize * colors + written as usize];
+                let transition = rule.get_transition(state, color);
+                writes.push((behind, color));
+                previous_ants.push(Ant {
+                    x: behind.0,
+                    y: behind.1,
+                    heading: (ant.heading + degree - transition.turn) % degree,
+                    state,
+                });
+            }
+
+            for (position, state) in writes.iter() {
+                write_cell(grid, journal, *position, *state);
+            }
+            std::mem::swap(ants, previous_ants);
+            *iteration -= 1;
+            performed += 1;
+        }
+
+        self.cycles.clear();
+        if let (Some(journal), Some(replaced)) = (&mut self.journal, replaced)
+            && performed > 0
+        {
+            journal.record_removed_ants(replaced.into_iter().enumerate().collect());
+            journal.record_added_ants(&self.ants);
+            journal.record_iteration(before, self.iteration);
+        }
+        if error.is_none() && performed < number_of_iterations && self.iteration == 0 {
+            error = Some(ReverseError::Start);
+        }
+        (performed, error)
+    }
+}
+
+// Write a cell, journaling its previous state while the undo history listens
+fn write_cell(
+    grid: &mut ChunkedGrid,
+    journal: &mut Option<Change>,
+    position: (i32, i32),
+    value: u8,
+) {
+    if let Some(journal) = journal {
+        journal.record_cell(position, grid.get(position), value);
+    }
+    grid.set(position, value);
+}
+
+// Periods a lone ant on a highway can skip after the one it is about to step through, before
+// the trail of a period, which lies within that many cells of the ant, could leave the
+// coordinates of the grid
+fn highway_room(ant: &Ant, cycle: Cycle) -> u64 {
+    let margin = cycle.period as i64;
+    let room = |position: i32, displacement: i32| match displacement.signum() {
+        1 => (i32::MAX as i64 - margin - position as i64) / displacement as i64,
+        -1 => (position as i64 - i32::MIN as i64 - margin) / -displacement as i64,
+        _ => i64::MAX,
+    };
+    let periods = room(ant.x, cycle.displacement.0).min(room(ant.y, cycle.displacement.1)) - 1;
+    periods.max(0) as u64
+}
+
+// Step a lone ant on a highway through one period, recording every step, then let it go on
+// along the highway for up to `steps` iterations in total, visiting about `work` cells. The
+// grid keeps the next periods as a highway that costs the same for any number of them. While
+// the undo history listens they are stamped into the grid instead, as long as they find the
+// colors the recorded one did, one chunk at most for every cell written. Returns the number of
+// iterations performed and of cells visited.
+#[allow(clippy::too_many_arguments)]
+fn skip_highway(
+    grid: &mut ChunkedGrid,
+    journal: &mut Option<Change>,
+    cycles: &mut CycleDetector,
+    rule: &Rule,
+    ant: &mut Ant,
+    cycle: Cycle,
+    steps: u64,
+    work: u64,
+) -> (u64, u64) {
+    let lattice = rule.get_lattice();
+    let start = ant.clone();
+    let mut recorded = Vec::with_capacity(cycle.period);
+    for _ in 0..cycle.period {
+        let position = (ant.x, ant.y);
+        let current_state = grid.get(position);
+        cycles.record(current_state, ant.state, ant.heading, position);
+        let transition = rule.get_transition(ant.state, current_state);
+        recorded.push(HighwayStep {
+            offset: (position.0 - start.x, position.1 - start.y),
+            heading: ant.heading,
+            state: ant.state,
+            read: current_state,
+            write: transition.write,
+        });
+        write_cell(grid, journal, position, transition.write);
+        move_ant(ant, transition, lattice, None);
+    }
+    let period = cycle.period as u64;
+    if (ant.x - start.x, ant.y - start.y) != cycle.displacement
+        || (ant.heading, ant.state) != (start.heading, start.state)
+    {
+        return (period, period);
+    }
+    // Within highway_room the cells of every period fit in the coordinates
+    let steps = (steps - period).min(highway_room(&start, cycle) * period);
+    let work = work.saturating_sub(period);
+
+    if journal.is_some() {
+        // Every cell written may take a new chunk
+        let writes = work.min((MAX_GRID_CHUNKS - grid.get_chunk_count()) as u64);
+        let (skipped, written) =
+            stamp_highway(grid, journal, &recorded, ant, cycle, steps / period, writes);
+        let shift = (
+            (skipped as i64 * cycle.displacement.0 as i64) as i32,
+            (skipped as i64 * cycle.displacement.1 as i64) as i32,
+        );
+        ant.x += shift.0;
+        ant.y += shift.1;
+        cycles.translate(shift);
+        return (period + skipped * period, period + written);
+    }
+
+    let highway = Highway::new((start.x, start.y), cycle.displacement, recorded);
+    let index = grid.add_highway(highway);
+    let (grown, checked) = grid.grow_highway(index, steps, work);
+    if grown == 0 {
+        grid.remove_highway(index);
+    } else {
+        move_to_highway_end(grid, cycles, ant, index, grown);
+    }
+    (period + grown, period + checked)
+}
+
+// Extend the highway a lone ant stands at the end of by up to `steps` iterations, checking
+// about `work` cells against the grid. Returns the number of iterations performed and of cells
+// checked, no iteration when the ant is not at the end of a highway.
+fn follow_highway(
+    grid: &mut ChunkedGrid,
+    cycles: &mut CycleDetector,
+    ant: &mut Ant,
+    steps: u64,
+    work: u64,
+) -> (u64, u64) {
+    let Some(index) = grid.find_highway((ant.x, ant.y), ant.heading, ant.state) else {
+        return (0, 0);
+    };
+    let highway = grid.get_highway(index);
+    let period = highway.get_period();
+    let cycle = Cycle {
+        period,
+        displacement: highway.get_displacement(),
+    };
+    let steps = steps.min(highway_room(ant, cycle) * period as u64);
+    let (grown, checked) = grid.grow_highway(index, steps, work);
+    if grown > 0 {
+        move_to_highway_end(grid, cycles, ant, index, grown);
+    }
+    (grown, checked)
+}
+
+// Put the ant where a highway that just grew by some steps ends
+fn move_to_highway_end(
+    grid: &ChunkedGrid,
+    cycles: &mut CycleDetector,
+    ant: &mut Ant,
+    index: usize,
+    grown: u64,
+) {
+    let highway = grid.get_highway(index);
+    let ((x, y), heading, state) = highway.get_pose(highway.get_end());
+    (ant.x, ant.y, ant.heading, ant.state) = (x, y, heading, state);
+    cycles.skip(grown);
+}
+
+// Stamp the periods after a recorded one into the grid while they find the colors it did,
+// which are either left by the period before them or still untouched, writing at most `writes`
+// cells. The ant stands at the start of the first of them. Returns the number of periods
+// stamped and of cells written.
+fn stamp_highway(
+    grid: &mut ChunkedGrid,
+    journal: &mut Option<Change>,
+    recorded: &[HighwayStep],
+    ant: &Ant,
+    cycle: Cycle,
+    periods: u64,
+    writes: u64,
+) -> (u64, u64) {
+    // Offset from the start of the period, color found there and color left
+    let mut trail: Vec<((i32, i32), u8, u8)> = Vec::new();
+    let mut indices: HashMap<(i32, i32), usize> = HashMap::new();
+    for step in recorded {
+        let index = *indices.entry(step.offset).or_insert_with(|| {
+            trail.push((step.offset, step.read, 0));
+            trail.len() - 1
+        });
+        trail[index].2 = step.write;
+    }
+    let periods = periods.min(writes / trail.len() as u64);
+
+    let (dx, dy) = cycle.displacement;
+    let cell = |offset: (i32, i32), period: u64| {
+        (
+            (ant.x as i64 + offset.0 as i64 + period as i64 * dx as i64) as i32,
+            (ant.y as i64 + offset.1 as i64 + period as i64 * dy as i64) as i32,
+        )
+    };
+    let mut stamped = 0;
+    while stamped < periods {
+        if trail
+            .iter()
+            .any(|(offset, found, _)| grid.get(cell(*offset, stamped)) != *found)
+        {
+            break;
+        }
+        for (offset, _, left) in &trail {
+            write_cell(grid, journal, cell(*offset, stamped), *left);
+        }
+        stamped += 1;
+    }
+    (stamped, stamped * trail.len() as u64)
+}
+
+// Turn an ant, change its internal state and move it forward. Over the edge of a world the ant
+// comes back from the opposite edge, turns around against a wall, or is left outside to be removed.
+fn move_ant(ant: &mut Ant, transition: &Transition, lattice: Lattice, world: Option<World>) {
+    let degree = lattice.get_degree();
+    ant.heading = (ant.heading + transition.turn) % degree;
+    ant.state = transition.next_state;
+    // Never past the coordinates, leaves_grid stops the simulation before such a move
+    let Some(next) = lattice.advance((ant.x, ant.y), ant.heading) else {
+        return;
+    };
+    match world.map_or(Landing::Cell(next), |world| world.land(next)) {
+        Landing::Cell(position) => (ant.x, ant.y) = position,
+        Landing::Wall => ant.heading = (ant.heading + degree / 2) % degree,
+        Landing::Outside => (ant.x, ant.y) = next,
+    }
+}
+
+// Whether the next move of an ant takes it past the coordinates of the grid, only ants on the
+// outermost cells need their transition looked up
+fn leaves_grid(grid: &ChunkedGrid, rule: &Rule, ant: &Ant) -> bool {
+    let outermost = |value: i32| value == i32::MIN || value == i32::MAX;
+    if !outermost(ant.x) && !outermost(ant.y) {
+        return false;
+    }
+    let lattice = rule.get_lattice();
+    let transition = rule.get_transition(ant.state, grid.get((ant.x, ant.y)));
+    let heading = (ant.heading + transition.turn) % lattice.get_degree();
+    lattice.advance((ant.x, ant.y), heading).is_none()
+}
+
+// Cell an ant stepped from, behind it and across the edge of a wrapping world. None when it
+// would be past the coordinates of the grid, the ant cannot come from there.
+fn cell_behind(ant: &Ant, lattice: Lattice, world: Option<World>) -> Option<(i32, i32)> {
+    let degree = lattice.get_degree();
+    let behind = lattice.advance((ant.x, ant.y), (ant.heading + degree / 2) % degree)?;
+    match world.map(|world| world.land(behind)) {
+        Some(Landing::Cell(position)) => Some(position),
+        _ => Some(behind),
+    }
+}
+
+// Move every ant with the ants split in ranges stepped by the pool, pushing the cell and
+// color written by each ant in the order of the ants
+fn move_ants_parallel(
+    pool: &ThreadPool,
+    grid: GridReader,
+    ants: &mut [Ant],
+    writes: &mut Vec<((i32, i32), u8)>,
+    rule: &Rule,
+    world: Option<World>,
+) {
+    let lattice = rule.get_lattice();
+    writes.resize(ants.len(), ((0, 0), 0));
+    pool.install(|| {
+        ants.par_chunks_mut(PARALLEL_CHUNK_ANTS)
+            .zip(writes.par_chunks_mut(PARALLEL_CHUNK_ANTS))
+            .for_each(|(ants, writes)| {
+                let mut grid = grid.clone();
+                for (ant, write) in ants.iter_mut().zip(writes) {
+                    let position = (ant.x, ant.y);
+                    let transition = rule.get_transition(ant.state, grid.get(position));
+                    *write = (position, transition.write);
+                    move_ant(ant, transition, lattice, world);
+                }
+            });
+    });
+}
+
+impl Default for Gamestate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gamemode for Gamestate {
+    fn get_mode_name(&self) -> &str {
+        "Langton's Ant"
+    }
+
+    fn step(&mut self, number_of_iterations: u64) -> u64 {
+        Gamestate::step(self, number_of_iterations)
+    }
+
+    fn step_frame(&mut self, number_of_iterations: u64) -> u64 {
+        self.advance(number_of_iterations, MAX_UPDATE_WORK)
+    }
+
+    fn update(&mut self, number_of_iterations: u64) -> u64 {
+        Gamestate::update(self, number_of_iterations)
+    }
+
+    fn reset(&mut self) {
+        Gamestate::reset(self)
+    }
+
+    fn get_pause_state(&self) -> bool {
+        Gamestate::get_pause_state(self)
+    }
+
+    fn set_pause_state(&mut self, value: bool) {
+        Gamestate::set_pause_state(self, value)
+    }
+
+    fn get_iteration(&self) -> u128 {
+        self.iteration
+    }
+
+    fn get_speed(&self) -> u64 {
+        self.speed
+    }
+
+    fn set_speed(&mut self, value: u64) {
+        Gamestate::set_speed(self, value)
+    }
+
+    fn get_grid(&self) -> &ChunkedGrid {
+        &self.grid
+    }
+
+    fn clear_grid(&mut self) {
+        Gamestate::clear_grid(self)
+    }
+
+    fn get_lattice(&self) -> Lattice {
+        self.rules[self.selected_rule].get_lattice()
+    }
+
+    fn get_state_colors(&self) -> Vec<u32> {
+        self.rules[self.selected_rule].get_colors().to_vec()
+    }
+
+    // Drop an ant on every cell of the brush
+    fn place(&mut self, position: (i32, i32)) {
+        self.add_ants(position)
+    }
+
+    // States past the end of the rule are painted with its last state
+    fn set_cell(&mut self, position: (i32, i32), state: u8) {
+        let last_state = self.get_rule_length().saturating_sub(1);
+        self.set_grid_value(position, state.min(last_state));
+        self.cycles.clear();
+    }
+
+    fn get_cursor_dimensions(&self) -> (u8, u8) {
+        self.cursor_size
+    }
+
+    fn set_cursor_dimensions(&mut self, dimensions: (u8, u8)) {
+        Gamestate::set_cursor_dimensions(self, dimensions)
+    }
+
+    fn get_rule_names(&self) -> Vec<String> {
+        self.rules
+            .iter()
+            .map(|rule| rule.get_name().to_string())
+            .collect()
+    }
+
+    fn get_selected_rule(&self) -> usize {
+        self.selected_rule
+    }
+
+    fn select_rule(&mut self, rule_number: usize) {
+        Gamestate::select_rule(self, rule_number)
+    }
+
+    fn as_langton(&self) -> Option<&Gamestate> {
+        Some(self)
+    }
+
+    fn as_langton_mut(&mut self) -> Option<&mut Gamestate> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Painted cells, ants in order and iteration, everything two runs must agree on
+    type Snapshot = (Vec<((i32, i32), u8)>, Vec<(i32, i32, u8, u8)>, u128);
+
+    fn snapshot(gamestate: &Gamestate) -> Snapshot {
+        let mut cells: Vec<_> = gamestate
+            .get_grid()
+            .iter()
+            .filter(|(_, state)| *state != 0)
+            .collect();
+        cells.sort();
+        let ants = gamestate
+            .get_ants()
+            .map(|ant| (ant.x, ant.y, ant.heading, ant.state))
+            .collect();
+        (cells, ants, *gamestate.get_iteration())
+    }
+
+    // Ants three cells apart in rows of 97, with every heading
+    fn scattered_ants(count: i32) -> Gamestate {
+        let mut gamestate = Gamestate::new();
+        for index in 0..count {
+            let direction = Direction::Up.rotate(index as isize);
+            gamestate.add_ant(Ant::place_ant(index % 97 * 3, index / 97 * 3, direction));
+        }
+        gamestate
+    }
+
+    #[test]
+    fn step_back_returns_to_the_start() {
+        let mut gamestate = scattered_ants(12);
+        let start = snapshot(&gamestate);
+        assert_eq!(gamestate.step(3000), 3000);
+        assert_eq!(gamestate.step_back(3000), Ok(3000));
+        assert_eq!(snapshot(&gamestate), start);
+        assert_eq!(gamestate.step_back(1), Err(ReverseError::Start));
+    }
+
+    #[test]
+    fn stepping_back_in_a_frame_is_bounded_by_the_work() {
+        let mut gamestate = scattered_ants(1 << 12);
+        assert_eq!(gamestate.step(600), 600);
+        let frame = MAX_UPDATE_WORK >> 12;
+        assert_eq!(gamestate.step_back_frame(u64::MAX), Ok(frame));
+        assert_eq!(*gamestate.get_iteration(), 600 - frame as u128);
+
+        gamestate.set_reverse_state(true);
+        gamestate.set_pause_state(false);
+        assert_eq!(gamestate.update(u64::MAX), frame);
+        assert!(!gamestate.get_pause_state());
+        assert_eq!(gamestate.take_reverse_error(), None);
+    }
+
+    // The classic ant at the origin, skipping highway periods or not
+    fn classic_ant(fast_forward: bool) -> Gamestate {
+        let mut gamestate = Gamestate::new();
+        gamestate.add_ant(Ant::place_ant(0, 0, Direction::Up));
+        gamestate.set_fast_forward(fast_forward);
+        gamestate
+    }
+
+    #[test]
+    fn skipping_highway_periods_matches_stepping() {
+        let mut stepped = classic_ant(false);
+        let mut skipped = classic_ant(true);
+        assert_eq!(stepped.step(200_000), 200_000);
+        for _ in 0..4 {
+            assert_eq!(skipped.step(50_000), 50_000);
+        }
+        assert_eq!(snapshot(&skipped), snapshot(&stepped));
+        let grid = skipped.get_grid();
+        assert_eq!(grid.len(), grid.iter().count());
+        assert_eq!(grid.bounding_box(), stepped.get_grid().bounding_box());
+        assert_eq!(skipped.get_state_counts(), stepped.get_state_counts());
+        assert!(grid.get_chunk_count() < stepped.get_grid().get_chunk_count());
+
+        // A world cuts the highway like the cells
+        let world = World::new((-300, -300), (400, 500), Edge::Wrap);
+        stepped.set_world(world);
+        skipped.set_world(world);
+        assert_eq!(snapshot(&skipped), snapshot(&stepped));
+        assert_eq!(skipped.get_painted_cells(), stepped.get_painted_cells());
+        assert_eq!(skipped.get_state_counts(), stepped.get_state_counts());
+    }
+
+    #[test]
+    fn highways_run_into_painted_cells() {
+        let mut stepped = classic_ant(false);
+        let mut skipped = classic_ant(true);
+        assert_eq!(stepped.step(15_000), 15_000);
+        assert_eq!(skipped.step(15_000), 15_000);
+        let cycle = stepped.get_cycle().unwrap();
+        let ant = stepped.get_ants().next().unwrap();
+        let ahead = (
+            ant.x + cycle.displacement.0 * 300,
+            ant.y + cycle.displacement.1 * 300,
+        );
+        for gamestate in [&mut stepped, &mut skipped] {
+            for offset in 0..9 {
+                gamestate.set_grid_value((ahead.0 + offset % 3, ahead.1 + offset / 3), 1);
+            }
+        }
+        assert_eq!(stepped.step(100_000), 100_000);
+        assert_eq!(skipped.step(100_000), 100_000);
+        assert_eq!(snapshot(&skipped), snapshot(&stepped));
+    }
+
+    #[test]
+    fn highways_cost_the_same_for_any_number_of_periods() {
+        let mut once = classic_ant(true);
+        let mut split = classic_ant(true);
+        assert_eq!(once.step(10_000_000_000), 10_000_000_000);
+        for _ in 0..10 {
+            assert_eq!(split.step(1_000_000_000), 1_000_000_000);
+        }
+        let ants = |gamestate: &Gamestate| {
+            gamestate
+                .get_ants()
+                .map(|ant| (ant.x, ant.y, ant.heading, ant.state))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ants(&once), ants(&split));
+        assert_eq!(once.get_painted_cells(), split.get_painted_cells());
+        assert_eq!(once.get_bounding_box(), split.get_bounding_box());
+        assert!(once.get_grid().get_chunk_count() < 64);
+
+        // Cells of the trail are read and written like any other
+        let ant = once.get_ants().next().unwrap();
+        let cycle = once.get_cycle().unwrap();
+        let behind = (
+            ant.x - cycle.displacement.0 * 1000,
+            ant.y - cycle.displacement.1 * 1000,
+        );
+        let painted = once.get_painted_cells();
+        assert_eq!(once.get_grid_value(behind), split.get_grid_value(behind));
+        let value = 1 - once.get_grid_value(behind);
+        once.set_grid_value(behind, value);
+        assert_eq!(once.get_grid_value(behind), value);
+        let expected = if value == 0 { painted - 1 } else { painted + 1 };
+        assert_eq!(once.get_painted_cells(), expected);
+    }
+
+    #[test]
+    fn invalid_rules_report_the_position_of_the_problem() {
+        let error = |notation: &str| Rule::new("Test", notation, (0, 0xFFFFFF)).err();
+        assert_eq!(error(""), Some(RuleError::Empty));
+        assert_eq!(
+            error("RLXR"),
+            Some(RuleError::InvalidTurn {
+                character: 'X',
+                position: 3
+            })
+        );
+        assert_eq!(
+            error("L1R3N"),
+            Some(RuleError::InvalidHexTurn {
+                token: "R3".to_string(),
+                position: 3
+            })
+        );
+        assert_eq!(
+            error(&"R".repeat(MAX_RULE_STATES + 1)),
+            Some(RuleError::TooManyStates(MAX_RULE_STATES + 1))
+        );
+    }
+
+    #[test]
+    fn hexagonal_rules_turn_in_sixths() {
+        let rule = Rule::new("Test", "L2NUR1", (0, 0xFFFFFF)).unwrap();
+        assert_eq!(rule.get_lattice(), Lattice::Hexagonal);
+        let turns: Vec<u8> = (0..4)
+            .map(|color| rule.get_transition(0, color).turn)
+            .collect();
+        assert_eq!(turns, vec![4, 0, 3, 1]);
+        // The last color cycles back to the first
+        assert_eq!(rule.get_transition(0, 3).write, 0);
+    }
+
+    #[test]
+    fn lattice_neighbours_are_distinct_and_opposite_headings_cancel() {
+        for lattice in [Lattice::Square, Lattice::Hexagonal] {
+            let degree = lattice.get_degree();
+            let mut neighbours: Vec<_> = (0..degree)
+                .map(|heading| lattice.advance((5, -5), heading).unwrap())
+                .collect();
+            for heading in 0..degree {
+                let there = lattice.advance((5, -5), heading).unwrap();
+                let back = lattice.advance(there, (heading + degree / 2) % degree);
+                assert_eq!(back, Some((5, -5)));
+            }
+            neighbours.sort();
+            neighbours.dedup();
+            assert_eq!(neighbours.len(), degree as usize);
+        }
+        assert_eq!(
+            Lattice::Hexagonal.advance((0, 0), HexDirection::NorthEast.as_index() as u8),
+            Some((1, -1))
+        );
+    }
+
+    #[test]
+    fn single_turn_rules_have_one_color() {
+        let rule = Rule::new("Test", "R", (0x000000, 0xFFFFFF)).unwrap();
+        assert_eq!(rule.get_colors(), &[0x000000]);
+    }
+
+    #[test]
+    fn parallel_stepping_matches_sequential() {
+        let mut sequential = scattered_ants(PARALLEL_MIN_ANTS as i32 + 500);
+        let mut parallel = scattered_ants(PARALLEL_MIN_ANTS as i32 + 500);
+        sequential.set_thread_count(1);
+        parallel.set_thread_count(2);
+        assert_eq!(sequential.step(40), 40);
+        assert_eq!(parallel.step(40), 40);
+        assert_eq!(snapshot(&sequential), snapshot(&parallel));
+    }
+
+    #[test]
+    fn the_earliest_ant_of_a_shared_cell_writes_it() {
+        let mut gamestate = Gamestate::new();
+        gamestate.set_thread_count(1);
+        gamestate.add_ant(Ant::place_ant(0, 0, Direction::Up));
+        gamestate.add_ant(Ant::place_ant(0, 0, Direction::Down));
+        gamestate.set_grid_value((0, 0), 1);
+        gamestate.step(1);
+        // Both read color 1 and turn left, the cell goes back to 0 once
+        assert_eq!(gamestate.get_grid_value((0, 0)), 0);
+        let ants: Vec<_> = gamestate.get_ants().map(|ant| (ant.x, ant.y)).collect();
+        assert_eq!(ants, vec![(-1, 0), (1, 0)]);
+    }
+}
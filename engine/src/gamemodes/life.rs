@@ -0,0 +1,325 @@
+use crate::gamemodes::{Gamemode, MAX_SPEED, MAX_UPDATE_WORK};
+use crate::grid::ChunkedGrid;
+use std::collections::HashMap;
+
+// Live cells use state 1 of the grid
+const LIVE: u8 = 1;
+const LIVE_COLOR: u32 = 0xF0F0F0;
+
+/// Life-like rule, written in the B/S notation ("B3/S23" for Conway's Game of Life)
+#[derive(Clone)]
+pub struct LifeRule {
+    name: String,
+    // Indexed by the number of live neighbours
+    birth: [bool; 9],
+    survival: [bool; 9],
+}
+
+impl LifeRule {
+    // Birth on 0 neighbours would fill the unbounded grid in one generation, it is ignored
+    pub fn new(name: &str, birth: &[u8], survival: &[u8]) -> Self {
+        let mut rule = LifeRule {
+            name: name.to_string(),
+            birth: [false; 9],
+            survival: [false; 9],
+        };
+        for count in birth.iter().filter(|count| (1..=8).contains(*count)) {
+            rule.birth[*count as usize] = true;
+        }
+        for count in survival.iter().filter(|count| **count <= 8) {
+            rule.survival[*count as usize] = true;
+        }
+        rule
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_notation(&self) -> String {
+        let counts = |flags: &[bool; 9]| -> String {
+            (0..9)
+                .filter(|count| flags[*count])
+                .map(|count| count.to_string())
+                .collect()
+        };
+        format!("B{}/S{}", counts(&self.birth), counts(&self.survival))
+    }
+
+    pub fn is_born(&self, neighbours: u8) -> bool {
+        self.birth[neighbours as usize]
+    }
+
+    pub fn survives(&self, neighbours: u8) -> bool {
+        self.survival[neighbours as usize]
+    }
+}
+
+/// Conway's Game of Life and the other Life-like automata on the square grid
+pub struct Life {
+    grid: ChunkedGrid,
+    paused: bool,
+    iteration: u128,
+    speed: u64,
+    cursor_size: (u8, u8),
+    max_cursor_size: u8,
+    selected_rule: usize,
+    rules: Vec<LifeRule>,
+}
+
+impl Life {
+    pub fn new() -> Life {
+        Life {
+            grid: ChunkedGrid::new(),
+            paused: true,
+            iteration: 0,
+            speed: 1,
+            cursor_size: (1, 1),
+            max_cursor_size: 10,
+            selected_rule: 0,
+            rules: Life::builtin_rules(),
+        }
+    }
+
+    fn builtin_rules() -> Vec<LifeRule> {
+        vec![
+            LifeRule::new("Conway", &[3], &[2, 3]),
+            LifeRule::new("HighLife", &[3, 6], &[2, 3]),
+            LifeRule::new("Seeds", &[2], &[]),
+            LifeRule::new("Day & Night", &[3, 6, 7, 8], &[3, 4, 6, 7, 8]),
+            LifeRule::new("Life without Death", &[3], &[0, 1, 2, 3, 4, 5, 6, 7, 8]),
+            LifeRule::new("Maze", &[3], &[1, 2, 3, 4, 5]),
+        ]
+    }
+
+    pub fn get_rule(&self) -> &LifeRule {
+        &self.rules[self.selected_rule]
+    }
+
+    // Compute generations until the number asked for, or until the live cells visited exceed the
+    // work allowed. At least one generation is computed.
+    fn generations(&mut self, number_of_generations: u64, work: u64) -> u64 {
+        let mut performed = 0;
+        let mut visited = 0;
+        while performed < number_of_generations && (performed == 0 || visited < work) {
+            // Nothing is ever born on an empty grid
+            if self.grid.is_empty() {
+                performed = number_of_generations;
+                break;
+            }
+            visited += self.grid.len() as u64;
+            self.next_generation();
+            performed += 1;
+        }
+        self.iteration += performed as u128;
+        performed
+    }
+
+    // Compute one generation from the neighbour counts of the live cells. The grid ends with
+    // the coordinates, cells past them are always dead.
+    fn next_generation(&mut self) {
+        let rule = &self.rules[self.selected_rule];
+
+        let mut neighbours: HashMap<(i32, i32), u8> = HashMap::new();
+        for ((x, y), _) in self.grid.iter() {
+            for offset_y in -1..=1 {
+                for offset_x in -1..=1 {
+                    if offset_x == 0 && offset_y == 0 {
+                        continue;
+                    }
+                    if let (Some(x), Some(y)) = (x.checked_add(offset_x), y.checked_add(offset_y)) {
+                        *neighbours.entry((x, y)).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+
+        // Live cells without any live neighbour are missing from the counts
+        let mut changes: Vec<((i32, i32), u8)> = self
+            .grid
+            .iter()
+            .filter(|(position, _)| !neighbours.contains_key(position) && !rule.survives(0))
+            .map(|(position, _)| (position, 0))
+            .collect();
+        for (position, count) in neighbours {
+            let alive = self.grid.get(position) != 0;
+            let next = if alive {
+                rule.survives(count)
+            } else {
+                rule.is_born(count)
+            };
+            if next != alive {
+                changes.push((position, if next { LIVE } else { 0 }));
+            }
+        }
+
+        for (position, value) in changes {
+            self.grid.set(position, value);
+        }
+    }
+}
+
+impl Default for Life {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gamemode for Life {
+    fn get_mode_name(&self) -> &str {
+        "Game of Life"
+    }
+
+    fn step(&mut self, number_of_iterations: u64) -> u64 {
+        self.generations(number_of_iterations, u64::MAX)
+    }
+
+    fn step_frame(&mut self, number_of_iterations: u64) -> u64 {
+        self.generations(number_of_iterations, MAX_UPDATE_WORK)
+    }
+
+    fn reset(&mut self) {
+        self.grid.clear();
+        self.iteration = 0;
+    }
+
+    fn get_pause_state(&self) -> bool {
+        self.paused
+    }
+
+    fn set_pause_state(&mut self, value: bool) {
+        self.paused = value
+    }
+
+    fn get_iteration(&self) -> u128 {
+        self.iteration
+    }
+
+    fn get_speed(&self) -> u64 {
+        self.speed
+    }
+
+    fn set_speed(&mut self, value: u64) {
+        self.speed = value.min(MAX_SPEED)
+    }
+
+    fn get_grid(&self) -> &ChunkedGrid {
+        &self.grid
+    }
+
+    fn clear_grid(&mut self) {
+        self.grid.clear()
+    }
+
+    fn get_state_colors(&self) -> Vec<u32> {
+        vec![0x000000, LIVE_COLOR]
+    }
+
+    // Bring every cell of the brush to life
+    fn place(&mut self, position: (i32, i32)) {
+        for x in 0..self.cursor_size.0 {
+            for y in 0..self.cursor_size.1 {
+                self.grid
+                    .set((position.0 + x as i32, position.1 + y as i32), LIVE);
+            }
+        }
+    }
+
+    // Every state but the background is a live cell
+    fn set_cell(&mut self, position: (i32, i32), state: u8) {
+        self.grid.set(position, if state == 0 { 0 } else { LIVE });
+    }
+
+    fn get_cursor_dimensions(&self) -> (u8, u8) {
+        self.cursor_size
+    }
+
+    fn set_cursor_dimensions(&mut self, dimensions: (u8, u8)) {
+        self.cursor_size = (
+            dimensions.0.min(self.max_cursor_size),
+            dimensions.1.min(self.max_cursor_size),
+        );
+    }
+
+    fn get_rule_names(&self) -> Vec<String> {
+        self.rules
+            .iter()
+            .map(|rule| rule.get_name().to_string())
+            .collect()
+    }
+
+    fn get_selected_rule(&self) -> usize {
+        self.selected_rule
+    }
+
+    fn select_rule(&mut self, rule_number: usize) {
+        if rule_number < self.rules.len() {
+            self.selected_rule = rule_number
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn live_cells(life: &Life) -> Vec<(i32, i32)> {
+        let mut cells: Vec<_> = life
+            .get_grid()
+            .iter()
+            .filter(|(_, state)| *state == LIVE)
+            .map(|(position, _)| position)
+            .collect();
+        cells.sort();
+        cells
+    }
+
+    fn with_cells(cells: &[(i32, i32)]) -> Life {
+        let mut life = Life::new();
+        for position in cells {
+            life.set_cell(*position, LIVE);
+        }
+        life
+    }
+
+    #[test]
+    fn blinkers_have_a_period_of_two() {
+        let mut life = with_cells(&[(-1, 0), (0, 0), (1, 0)]);
+        life.step(1);
+        assert_eq!(live_cells(&life), vec![(0, -1), (0, 0), (0, 1)]);
+        life.step(1);
+        assert_eq!(live_cells(&life), vec![(-1, 0), (0, 0), (1, 0)]);
+        assert_eq!(life.get_iteration(), 2);
+    }
+
+    #[test]
+    fn gliders_move_one_cell_diagonally_every_four_generations() {
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let mut life = with_cells(&glider);
+        life.step(4);
+        let mut moved: Vec<_> = glider.iter().map(|(x, y)| (x + 1, y + 1)).collect();
+        moved.sort();
+        assert_eq!(live_cells(&life), moved);
+    }
+
+    #[test]
+    fn cells_past_the_coordinates_stay_dead() {
+        let mut life = with_cells(&[
+            (i32::MAX, i32::MIN),
+            (i32::MAX - 1, i32::MIN),
+            (i32::MAX, i32::MIN + 1),
+        ]);
+        life.step(1);
+        assert_eq!(live_cells(&life).len(), 4);
+    }
+
+    #[test]
+    fn generations_stop_after_their_share_of_work() {
+        // A blinker visits its 3 live cells every generation
+        let mut life = with_cells(&[(-1, 0), (0, 0), (1, 0)]);
+        assert_eq!(life.generations(1 << 40, 30), 10);
+        assert_eq!(life.generations(1 << 40, 0), 1);
+        let mut empty = Life::new();
+        assert_eq!(empty.step_frame(1 << 40), 1 << 40);
+    }
+}
@@ -0,0 +1,141 @@
+pub mod cycle;
+pub mod langton;
+pub mod life;
+pub mod turmite;
+
+use crate::gamemodes::langton::{Gamestate, Lattice};
+use crate::grid::ChunkedGrid;
+use std::collections::HashSet;
+
+// Largest area changed by a flood fill
+pub const MAX_FILL_CELLS: usize = 1 << 20;
+// Fastest speed, in iterations per update
+pub const MAX_SPEED: u64 = 1 << 40;
+// Cells read or written by one call to update, so that a frame stays short whatever the speed
+pub const MAX_UPDATE_WORK: u64 = 1 << 20;
+
+/// Interface shared by every automaton the frontend can run.
+/// Ant specific features are reached through `as_langton`.
+pub trait Gamemode {
+    // Name shown in the gamemode menu
+    fn get_mode_name(&self) -> &str;
+
+    // Advance the simulation regardless of the pause state, returning the number of iterations performed
+    fn step(&mut self, number_of_iterations: u64) -> u64;
+
+    // Same as step, but stopping once about MAX_UPDATE_WORK cells were visited so that a frame
+    // stays short, at least one iteration is performed
+    fn step_frame(&mut self, number_of_iterations: u64) -> u64;
+
+    // Advance the simulation unless it is paused, within the work of one frame
+    fn update(&mut self, number_of_iterations: u64) -> u64 {
+        if self.get_pause_state() {
+            return 0;
+        }
+        self.step_frame(number_of_iterations)
+    }
+
+    // Clear the grid and everything placed on it
+    fn reset(&mut self);
+
+    fn get_pause_state(&self) -> bool;
+
+    fn set_pause_state(&mut self, value: bool);
+
+    fn invert_pause_state(&mut self) {
+        self.set_pause_state(!self.get_pause_state());
+    }
+
+    fn get_iteration(&self) -> u128;
+
+    fn get_speed(&self) -> u64;
+
+    // Speeds past MAX_SPEED are reduced to it
+    fn set_speed(&mut self, value: u64);
+
+    // Cells
+    fn get_grid(&self) -> &ChunkedGrid;
+
+    fn clear_grid(&mut self);
+
+    fn get_lattice(&self) -> Lattice {
+        Lattice::Square
+    }
+
+    fn get_painted_cells(&self) -> usize {
+        self.get_grid().len()
+    }
+
+    // Colors of every cell state as 0xRRGGBB, state 0 is the background
+    fn get_state_colors(&self) -> Vec<u32>;
+
+    // Placement with the brush at a grid position
+    fn place(&mut self, position: (i32, i32));
+
+    // Set a single cell to a state of the current rule
+    fn set_cell(&mut self, position: (i32, i32), state: u8);
+
+    // Set every cell of the brush to a state
+    fn paint(&mut self, position: (i32, i32), state: u8) {
+        let (width, height) = self.get_cursor_dimensions();
+        for x in 0..width {
+            for y in 0..height {
+                self.set_cell((position.0 + x as i32, position.1 + y as i32), state);
+            }
+        }
+    }
+
+    // Set the connected area of cells sharing the state of a position, returning the number of
+    // cells changed. The background around the pattern is unbounded, so an area reaching past
+    // the painted cells, or larger than MAX_FILL_CELLS, is left untouched and gives None.
+    fn flood_fill(&mut self, position: (i32, i32), state: u8) -> Option<usize> {
+        let grid = self.get_grid();
+        let target = grid.get(position);
+        if target == state {
+            return Some(0);
+        }
+        let (min_x, min_y, max_x, max_y) = grid.bounding_box()?;
+        let lattice = self.get_lattice();
+
+        let mut area = HashSet::from([position]);
+        let mut pending = vec![position];
+        while let Some((x, y)) = pending.pop() {
+            if x < min_x || x > max_x || y < min_y || y > max_y || area.len() > MAX_FILL_CELLS {
+                return None;
+            }
+            for heading in 0..lattice.get_degree() {
+                let Some(neighbour) = lattice.advance((x, y), heading) else {
+                    continue;
+                };
+                if grid.get(neighbour) == target && area.insert(neighbour) {
+                    pending.push(neighbour);
+                }
+            }
+        }
+
+        for cell in &area {
+            self.set_cell(*cell, state);
+        }
+        Some(area.len())
+    }
+
+    fn get_cursor_dimensions(&self) -> (u8, u8);
+
+    fn set_cursor_dimensions(&mut self, dimensions: (u8, u8));
+
+    // Rules
+    fn get_rule_names(&self) -> Vec<String>;
+
+    fn get_selected_rule(&self) -> usize;
+
+    fn select_rule(&mut self, rule_number: usize);
+
+    // Ants only exist in the Langton gamemode
+    fn as_langton(&self) -> Option<&Gamestate> {
+        None
+    }
+
+    fn as_langton_mut(&mut self) -> Option<&mut Gamestate> {
+        None
+    }
+}
@@ -0,0 +1,356 @@
+use crate::gamemodes::langton::{HexTurn, MAX_RULE_STATES, MAX_TURMITE_STATES, RuleError, Turn};
+
+/// What an ant does on a cell: the color written, the turn made and its next internal state
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Transition {
+    pub write: u8,
+    // Clockwise steps between headings of the rule's lattice
+    pub turn: u8,
+    pub next_state: u8,
+}
+
+/// Transition table of a turmite, indexed by (ant state, cell color).
+/// Written as `{{{write, turn, next}, ...one per color}, ...one per state}` where the
+/// turn is 1 (no turn), 2 (right), 4 (U-turn) or 8 (left).
+#[derive(Clone, PartialEq, Debug)]
+pub struct Turmite {
+    states: usize,
+    colors: usize,
+    table: Vec<Transition>,
+}
+
+// Nested lists of numbers, each node keeps its 1-based position in the notation
+enum Node {
+    Number(u32, usize),
+    List(Vec<Node>, usize),
+}
+
+impl Node {
+    fn position(&self) -> usize {
+        match self {
+            Node::Number(_, position) | Node::List(_, position) => *position,
+        }
+    }
+}
+
+impl Turmite {
+    // A Langton ant is a turmite with a single state that cycles through the colors
+    pub fn from_turns(turns: &[Turn]) -> Self {
+        let colors = turns.len();
+        Turmite {
+            states: 1,
+            colors,
+            table: turns
+                .iter()
+                .enumerate()
+                .map(|(color, turn)| Transition {
+                    write: ((color + 1) % colors) as u8,
+                    turn: turn.as_steps() as u8,
+                    next_state: 0,
+                })
+                .collect(),
+        }
+    }
+
+    // Same as from_turns with the six turns of the hexagonal lattice
+    pub fn from_hex_turns(turns: &[HexTurn]) -> Self {
+        let colors = turns.len();
+        Turmite {
+            states: 1,
+            colors,
+            table: turns
+                .iter()
+                .enumerate()
+                .map(|(color, turn)| Transition {
+                    write: ((color + 1) % colors) as u8,
+                    turn: turn.as_steps() as u8,
+                    next_state: 0,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn parse(notation: &str) -> Result<Self, RuleError> {
+        let characters: Vec<char> = notation.chars().collect();
+        let mut index = 0;
+        let root = Turmite::parse_node(&characters, &mut index)?;
+        Turmite::skip_whitespace(&characters, &mut index);
+        if index < characters.len() {
+            return Err(syntax_error(index, "the end of the table"));
+        }
+
+        let states = match root {
+            Node::List(states, _) if !states.is_empty() => states,
+            node => {
+                return Err(RuleError::TableSyntax {
+                    position: node.position(),
+                    message: "expected a non-empty list of states".to_string(),
+                });
+            }
+        };
+        if states.len() > MAX_TURMITE_STATES {
+            return Err(RuleError::TooManyAntStates(states.len()));
+        }
+
+        let mut colors = None;
+        let mut raw = Vec::new();
+        for (state, node) in states.iter().enumerate() {
+            let entries = match node {
+                Node::List(entries, _) if !entries.is_empty() => entries,
+                node => {
+                    return Err(RuleError::TableSyntax {
+                        position: node.position(),
+                        message: format!("expected a non-empty list of colors for state {}", state),
+                    });
+                }
+            };
+            let expected = *colors.get_or_insert(entries.len());
+            if entries.len() != expected {
+                return Err(RuleError::TableSyntax {
+                    position: node.position(),
+                    message: format!(
+                        "state {} has {} colors but state 0 has {}",
+                        state,
+                        entries.len(),
+                        expected
+                    ),
+                });
+            }
+            for (color, entry) in entries.iter().enumerate() {
+                raw.push((state, color, Turmite::parse_triple(entry)?));
+            }
+        }
+
+        let colors = colors.unwrap_or_default();
+        if colors > MAX_RULE_STATES {
+            return Err(RuleError::TooManyStates(colors));
+        }
+
+        let mut table = Vec::with_capacity(raw.len());
+        for (state, color, (write, turn, next_state)) in raw {
+            let entry_error = |message: String| RuleError::TableEntry {
+                state,
+                color,
+                message,
+            };
+            if write as usize >= colors {
+                return Err(entry_error(format!(
+                    "writes color {} out of {}",
+                    write, colors
+                )));
+            }
+            if next_state as usize >= states.len() {
+                return Err(entry_error(format!(
+                    "goes to state {} out of {}",
+                    next_state,
+                    states.len()
+                )));
+            }
+            let turn = match turn {
+                1 => Turn::None.as_steps() as u8,
+                2 => Turn::Right.as_steps() as u8,
+                4 => Turn::UTurn.as_steps() as u8,
+                8 => Turn::Left.as_steps() as u8,
+                _ => {
+                    return Err(entry_error(format!(
+                        "turn {} is not 1 (no turn), 2 (right), 4 (U-turn) or 8 (left)",
+                        turn
+                    )));
+                }
+            };
+            table.push(Transition {
+                write: write as u8,
+                turn,
+                next_state: next_state as u8,
+            });
+        }
+
+        Ok(Turmite {
+            states: states.len(),
+            colors,
+            table,
+        })
+    }
+
+    fn parse_triple(node: &Node) -> Result<(u32, u32, u32), RuleError> {
+        let error = RuleError::TableSyntax {
+            position: node.position(),
+            message: "expected {write, turn, next state}".to_string(),
+        };
+        match node {
+            Node::List(values, _) => match values.as_slice() {
+                [
+                    Node::Number(write, _),
+                    Node::Number(turn, _),
+                    Node::Number(next, _),
+                ] => Ok((*write, *turn, *next)),
+                _ => Err(error),
+            },
+            Node::Number(..) => Err(error),
+        }
+    }
+
+    fn skip_whitespace(characters: &[char], index: &mut usize) {
+        while *index < characters.len() && characters[*index].is_whitespace() {
+            *index += 1;
+        }
+    }
+
+    fn parse_node(characters: &[char], index: &mut usize) -> Result<Node, RuleError> {
+        Turmite::skip_whitespace(characters, index);
+        let start = *index;
+        match characters.get(start) {
+            Some('{') => {
+                *index += 1;
+                let mut items = Vec::new();
+                Turmite::skip_whitespace(characters, index);
+                if characters.get(*index) == Some(&'}') {
+                    *index += 1;
+                    return Ok(Node::List(items, start + 1));
+                }
+                loop {
+                    items.push(Turmite::parse_node(characters, index)?);
+                    Turmite::skip_whitespace(characters, index);
+                    match characters.get(*index) {
+                        Some(',') => *index += 1,
+                        Some('}') => {
+                            *index += 1;
+                            return Ok(Node::List(items, start + 1));
+                        }
+                        _ => return Err(syntax_error(*index, "',' or '}'")),
+                    }
+                }
+            }
+            Some(c) if c.is_ascii_digit() => {
+                while characters.get(*index).is_some_and(|c| c.is_ascii_digit()) {
+                    *index += 1;
+                }
+                let digits: String = characters[start..*index].iter().collect();
+                digits
+                    .parse()
+                    .map(|value| Node::Number(value, start + 1))
+                    .map_err(|_| RuleError::TableSyntax {
+                        position: start + 1,
+                        message: format!("number {} is too large", digits),
+                    })
+            }
+            _ => Err(syntax_error(start, "'{' or a number")),
+        }
+    }
+
+    pub fn get_transition(&self, state: u8, color: u8) -> &Transition {
+        &self.table[state as usize * self.colors + color as usize]
+    }
+
+    // Ant state and color that led to each (next state, written color), indexed like the table.
+    // Only exists when no two transitions write the same color and go to the same state.
+    pub fn get_inverse(&self) -> Option<Vec<(u8, u8)>> {
+        let mut inverse = vec![None; self.table.len()];
+        for (index, transition) in self.table.iter().enumerate() {
+            let origin = &mut inverse
+                [transition.next_state as usize * self.colors + transition.write as usize];
+            if origin.is_some() {
+                return None;
+            }
+            *origin = Some(((index / self.colors) as u8, (index % self.colors) as u8));
+        }
+        // As many transitions as entries, none of them can be left empty
+        inverse.into_iter().collect()
+    }
+
+    pub fn get_states(&self) -> usize {
+        self.states
+    }
+
+    pub fn get_colors(&self) -> usize {
+        self.colors
+    }
+
+    // Write the table back in the {{{write, turn, next}, ...}} notation
+    pub fn get_notation(&self) -> String {
+        let states: Vec<String> = self
+            .table
+            .chunks(self.colors)
+            .map(|row| {
+                let entries: Vec<String> = row
+                    .iter()
+                    .map(|transition| {
+                        // Tables only exist on the square lattice
+                        let turn = 1 << transition.turn;
+                        format!(
+                            "{{{},{},{}}}",
+                            transition.write, turn, transition.next_state
+                        )
+                    })
+                    .collect();
+                format!("{{{}}}", entries.join(","))
+            })
+            .collect();
+        format!("{{{}}}", states.join(","))
+    }
+}
+
+fn syntax_error(index: usize, expected: &str) -> RuleError {
+    RuleError::TableSyntax {
+        position: index + 1,
+        message: format!("expected {}", expected),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn syntax_position(notation: &str) -> Option<usize> {
+        match Turmite::parse(notation) {
+            Err(RuleError::TableSyntax { position, .. }) => Some(position),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn tables_parse_and_write_back() {
+        let notation = "{{{1,2,0},{0,8,0}}}";
+        let turmite = Turmite::parse(" { { {1, 2, 0}, {0, 8, 0} } } ").unwrap();
+        assert_eq!((turmite.get_states(), turmite.get_colors()), (1, 2));
+        assert_eq!(turmite.get_notation(), notation);
+        assert_eq!(
+            turmite.get_transition(0, 1),
+            &Transition {
+                write: 0,
+                turn: Turn::Left.as_steps() as u8,
+                next_state: 0
+            }
+        );
+    }
+
+    #[test]
+    fn syntax_errors_point_at_the_character() {
+        assert_eq!(syntax_position("{{{1,2,0},{0,8,0}}"), Some(19));
+        assert_eq!(syntax_position("{{{1,2,0};{0,8,0}}}"), Some(10));
+        assert_eq!(syntax_position("{{{1,2},{0,8,0}}}"), Some(3));
+        assert_eq!(syntax_position("{{{1,2,0},{0,8,0}}} x"), Some(21));
+        assert_eq!(syntax_position("{{{1,2,0}},{{0,8,0},{1,2,0}}}"), Some(12));
+    }
+
+    #[test]
+    fn unusable_entries_name_their_state_and_color() {
+        let entry = |notation: &str| match Turmite::parse(notation) {
+            Err(RuleError::TableEntry { state, color, .. }) => Some((state, color)),
+            _ => None,
+        };
+        assert_eq!(entry("{{{1,2,0},{2,8,0}}}"), Some((0, 1)));
+        assert_eq!(entry("{{{1,2,0},{0,8,0}},{{1,3,1},{0,8,0}}}"), Some((1, 0)));
+        assert_eq!(entry("{{{1,2,0},{0,8,2}},{{1,2,1},{0,8,0}}}"), Some((0, 1)));
+    }
+
+    #[test]
+    fn ant_states_are_limited_to_what_a_u8_holds() {
+        let table = |states: usize| format!("{{{}}}", vec!["{{0,1,0}}"; states].join(","));
+        assert!(Turmite::parse(&table(MAX_TURMITE_STATES)).is_ok());
+        assert_eq!(
+            Turmite::parse(&table(MAX_TURMITE_STATES + 1)),
+            Err(RuleError::TooManyAntStates(MAX_TURMITE_STATES + 1))
+        );
+    }
+}
@@ -0,0 +1,568 @@
+use crate::highway::{Highway, overlaps};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+
+// Chunks are square tiles of CHUNK_SIZE x CHUNK_SIZE cells
+pub const CHUNK_SHIFT: u32 = 6;
+pub const CHUNK_SIZE: i32 = 1 << CHUNK_SHIFT;
+pub const CHUNK_AREA: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+const CHUNK_MASK: i32 = CHUNK_SIZE - 1;
+
+// Marks the lookup cache as empty
+const NO_CHUNK: usize = usize::MAX;
+
+/// A tile of cell states, stored row by row
+#[derive(Clone)]
+pub struct Chunk {
+    cells: Box<[u8; CHUNK_AREA]>,
+    // Number of cells with a non-zero state
+    painted: usize,
+    // Grid version of the last change, lets renderers find modified chunks
+    version: u64,
+}
+
+impl Chunk {
+    fn new() -> Self {
+        Chunk {
+            cells: Box::new([0; CHUNK_AREA]),
+            painted: 0,
+            version: 0,
+        }
+    }
+
+    pub fn get_cells(&self) -> &[u8; CHUNK_AREA] {
+        &self.cells
+    }
+
+    pub fn get_painted(&self) -> usize {
+        self.painted
+    }
+
+    pub fn get_version(&self) -> u64 {
+        self.version
+    }
+}
+
+/// Unbounded grid of u8 states where state 0 is the background.
+/// Storage is allocated chunk by chunk when a non-zero state is first written,
+/// the trails of highways are kept as [`Highway`]s until something else is written on them.
+pub struct ChunkedGrid {
+    chunks: Vec<Chunk>,
+    index: HashMap<(i32, i32), usize>,
+    // Non-zero cells of the chunks
+    painted: usize,
+    // Their cells stand for the chunks that are not allocated
+    highways: Vec<Highway>,
+    // Incremented on every change, never reset
+    version: u64,
+    // Last chunk looked up, most accesses of a single ant hit it
+    last_lookup: Cell<((i32, i32), usize)>,
+}
+
+impl ChunkedGrid {
+    pub fn new() -> Self {
+        ChunkedGrid {
+            chunks: Vec::new(),
+            index: HashMap::new(),
+            painted: 0,
+            highways: Vec::new(),
+            version: 0,
+            last_lookup: Cell::new(((0, 0), NO_CHUNK)),
+        }
+    }
+
+    // Inclusive rectangle of the cells of a chunk
+    fn chunk_rectangle(chunk: (i32, i32)) -> (i32, i32, i32, i32) {
+        let (x, y) = (chunk.0 << CHUNK_SHIFT, chunk.1 << CHUNK_SHIFT);
+        (x, y, x + CHUNK_MASK, y + CHUNK_MASK)
+    }
+
+    // Split a cell position into its chunk coordinates and the offset inside the chunk
+    pub fn split_position(position: (i32, i32)) -> ((i32, i32), usize) {
+        let (x, y) = position;
+        let chunk = (x >> CHUNK_SHIFT, y >> CHUNK_SHIFT);
+        let offset = ((y & CHUNK_MASK) * CHUNK_SIZE + (x & CHUNK_MASK)) as usize;
+        (chunk, offset)
+    }
+
+    fn find_chunk(&self, chunk: (i32, i32)) -> Option<usize> {
+        let (last_chunk, last_index) = self.last_lookup.get();
+        if last_index != NO_CHUNK && last_chunk == chunk {
+            return Some(last_index);
+        }
+        let index = *self.index.get(&chunk)?;
+        self.last_lookup.set((chunk, index));
+        Some(index)
+    }
+
+    pub fn get(&self, position: (i32, i32)) -> u8 {
+        let (chunk, offset) = Self::split_position(position);
+        match self.find_chunk(chunk) {
+            Some(index) => self.chunks[index].cells[offset],
+            None => highway_value(&self.highways, position),
+        }
+    }
+
+    pub fn set(&mut self, position: (i32, i32), value: u8) {
+        let (chunk, offset) = Self::split_position(position);
+        let index = match self.find_chunk(chunk) {
+            Some(index) => index,
+            None => {
+                let highway_cells = self.take_highway_cells(chunk);
+                // Writing the background into a missing chunk changes nothing
+                if highway_cells.is_none() && value == 0 {
+                    return;
+                }
+                let data = highway_cells.unwrap_or_else(Chunk::new);
+                self.painted += data.painted;
+                self.chunks.push(data);
+                let index = self.chunks.len() - 1;
+                self.index.insert(chunk, index);
+                self.last_lookup.set((chunk, index));
+                index
+            }
+        };
+
+        let chunk = &mut self.chunks[index];
+        let previous = chunk.cells[offset];
+        if previous == value {
+            return;
+        }
+        chunk.cells[offset] = value;
+        self.version += 1;
+        chunk.version = self.version;
+        match (previous == 0, value == 0) {
+            (true, false) => {
+                chunk.painted += 1;
+                self.painted += 1;
+            }
+            (false, true) => {
+                chunk.painted -= 1;
+                self.painted -= 1;
+            }
+            _ => {}
+        }
+    }
+
+    // Copy the cells highways cover in a chunk about to be allocated, the highways stop
+    // counting them
+    fn take_highway_cells(&mut self, chunk: (i32, i32)) -> Option<Chunk> {
+        let rectangle = Self::chunk_rectangle(chunk);
+        let mut data: Option<Chunk> = None;
+        for highway in self.highways.iter_mut() {
+            for (position, state) in highway.hide_chunk(chunk, rectangle) {
+                let data = data.get_or_insert_with(Chunk::new);
+                data.cells[Self::split_position(position).1] = state;
+                if state != 0 {
+                    data.painted += 1;
+                }
+            }
+        }
+        if let Some(data) = data.as_mut() {
+            self.version += 1;
+            data.version = self.version;
+        }
+        data
+    }
+
+    pub fn get_version(&self) -> u64 {
+        self.version
+    }
+
+    // Read-only view that can be sent to other threads
+    pub fn reader(&self) -> GridReader<'_> {
+        GridReader {
+            chunks: &self.chunks,
+            index: &self.index,
+            highways: &self.highways,
+            last_lookup: ((0, 0), NO_CHUNK),
+        }
+    }
+
+    // Cells of a chunk, built from the highways covering it when it is not allocated
+    pub fn get_chunk(&self, chunk: (i32, i32)) -> Option<Cow<'_, Chunk>> {
+        if let Some(index) = self.find_chunk(chunk) {
+            return Some(Cow::Borrowed(&self.chunks[index]));
+        }
+        let rectangle = Self::chunk_rectangle(chunk);
+        let mut data: Option<Chunk> = None;
+        for highway in &self.highways {
+            for (position, state) in highway.cells_in(rectangle) {
+                let data = data.get_or_insert_with(Chunk::new);
+                data.cells[Self::split_position(position).1] = state;
+                data.version = data.version.max(highway.get_version());
+                if state != 0 {
+                    data.painted += 1;
+                }
+            }
+        }
+        data.map(Cow::Owned)
+    }
+
+    // Number of chunks allocated, each of them takes CHUNK_AREA bytes
+    pub fn get_chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    // Number of cells with a non-zero state
+    pub fn len(&self) -> usize {
+        self.painted + self.highways.iter().map(Highway::get_painted).sum::<u64>() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.index.clear();
+        self.painted = 0;
+        self.highways.clear();
+        self.version += 1;
+        self.last_lookup.set(((0, 0), NO_CHUNK));
+    }
+
+    // Iterate over every non-zero cell
+    pub fn iter(&self) -> impl Iterator<Item = ((i32, i32), u8)> + '_ {
+        self.chunk_iter().chain(
+            self.highways
+                .iter()
+                .flat_map(|highway| self.outside_chunks(highway.cells())),
+        )
+    }
+
+    // Iterate over the non-zero cells of the chunks
+    fn chunk_iter(&self) -> impl Iterator<Item = ((i32, i32), u8)> + '_ {
+        self.index
+            .iter()
+            .filter(|(_, index)| self.chunks[**index].painted > 0)
+            .flat_map(|(chunk, index)| Self::chunk_cells(*chunk, &self.chunks[*index]))
+    }
+
+    // Keep the non-zero cells of a highway the chunks do not hold
+    fn outside_chunks<'a>(
+        &'a self,
+        cells: impl Iterator<Item = ((i32, i32), u8)> + 'a,
+    ) -> impl Iterator<Item = ((i32, i32), u8)> + 'a {
+        cells.filter(|(position, state)| {
+            *state != 0 && !self.index.contains_key(&Self::split_position(*position).0)
+        })
+    }
+
+    // Iterate over the non-zero cells inside an inclusive rectangle
+    pub fn iter_region(
+        &self,
+        min_x: i32,
+        min_y: i32,
+        max_x: i32,
+        max_y: i32,
+    ) -> impl Iterator<Item = ((i32, i32), u8)> + '_ {
+        let (min_chunk, _) = Self::split_position((min_x, min_y));
+        let (max_chunk, _) = Self::split_position((max_x, max_y));
+        let region_chunks = (max_chunk.0 as i64 - min_chunk.0 as i64 + 1)
+            * (max_chunk.1 as i64 - min_chunk.1 as i64 + 1);
+
+        // Look the chunks up directly for small regions, otherwise scan the allocated ones
+        let selected: Vec<((i32, i32), usize)> = if region_chunks <= self.index.len() as i64 {
+            (min_chunk.1..=max_chunk.1)
+                .flat_map(|chunk_y| {
+                    (min_chunk.0..=max_chunk.0).map(move |chunk_x| (chunk_x, chunk_y))
+                })
+                .filter_map(|chunk| self.index.get(&chunk).map(|index| (chunk, *index)))
+                .collect()
+        } else {
+            self.index
+                .iter()
+                .filter(|((chunk_x, chunk_y), _)| {
+                    *chunk_x >= min_chunk.0
+                        && *chunk_x <= max_chunk.0
+                        && *chunk_y >= min_chunk.1
+                        && *chunk_y <= max_chunk.1
+                })
+                .map(|(chunk, index)| (*chunk, *index))
+                .collect()
+        };
+
+        let rectangle = (min_x, min_y, max_x, max_y);
+        selected
+            .into_iter()
+            .filter(|(_, index)| self.chunks[*index].painted > 0)
+            .flat_map(|(chunk, index)| Self::chunk_cells(chunk, &self.chunks[index]))
+            .filter(move |((x, y), _)| *x >= min_x && *x <= max_x && *y >= min_y && *y <= max_y)
+            .chain(
+                self.highways
+                    .iter()
+                    .flat_map(move |highway| self.outside_chunks(highway.cells_in(rectangle))),
+            )
+    }
+
+    fn chunk_cells(chunk: (i32, i32), data: &Chunk) -> impl Iterator<Item = ((i32, i32), u8)> + '_ {
+        let origin_x = chunk.0 << CHUNK_SHIFT;
+        let origin_y = chunk.1 << CHUNK_SHIFT;
+        data.cells
+            .iter()
+            .enumerate()
+            .filter(|(_, state)| **state != 0)
+            .map(move |(offset, state)| {
+                let x = origin_x + (offset as i32 & CHUNK_MASK);
+                let y = origin_y + (offset as i32 >> CHUNK_SHIFT);
+                ((x, y), *state)
+            })
+    }
+
+    // Smallest rectangle (min_x, min_y, max_x, max_y) containing every non-zero cell
+    // Highways only count their painted cells outside the chunks, their bounds may be larger
+    pub fn bounding_box(&self) -> Option<(i32, i32, i32, i32)> {
+        let cells = self
+            .chunk_iter()
+            .map(|((x, y), _)| (x, y, x, y))
+            .chain(self.highways.iter().filter_map(Highway::get_painted_bounds));
+        cells.fold(None, |bounds, (x, y, other_x, other_y)| match bounds {
+            None => Some((x, y, other_x, other_y)),
+            Some((min_x, min_y, max_x, max_y)) => Some((
+                min_x.min(x),
+                min_y.min(y),
+                max_x.max(other_x),
+                max_y.max(other_y),
+            )),
+        })
+    }
+
+    // Number of cells in each state below `states`, the background state 0 is left at zero
+    pub fn state_counts(&self, states: usize) -> Vec<usize> {
+        let mut counts = vec![0; states];
+        for (_, state) in self.chunk_iter() {
+            if let Some(count) = counts.get_mut(state as usize) {
+                *count += 1;
+            }
+        }
+        for highway in &self.highways {
+            for (state, cells) in highway.get_counts().enumerate().skip(1) {
+                if let Some(count) = counts.get_mut(state) {
+                    *count += cells as usize;
+                }
+            }
+        }
+        counts
+    }
+
+    // Add a highway whose cells are not written anywhere yet, returning its index
+    pub fn add_highway(&mut self, highway: Highway) -> usize {
+        self.highways.push(highway);
+        self.highways.len() - 1
+    }
+
+    pub fn get_highway(&self, index: usize) -> &Highway {
+        &self.highways[index]
+    }
+
+    // Remove a highway that covers no step
+    pub fn remove_highway(&mut self, index: usize) {
+        self.highways.remove(index);
+    }
+
+    // Index of the highway an ant with this pose stands at the end of
+    pub fn find_highway(&self, position: (i32, i32), heading: u8, state: u8) -> Option<usize> {
+        self.highways.iter().position(|highway| {
+            !highway.is_clipped()
+                && highway.get_pose(highway.get_end()) == (position, heading, state)
+        })
+    }
+
+    // Reset every cell outside an inclusive rectangle to the background
+    pub fn clip(&mut self, rectangle: (i32, i32, i32, i32)) {
+        let outside: Vec<(i32, i32)> = self
+            .chunk_iter()
+            .map(|(position, _)| position)
+            .filter(|position| {
+                !overlaps((position.0, position.1, position.0, position.1), rectangle)
+            })
+            .collect();
+        for position in outside {
+            self.set(position, 0);
+        }
+        self.version += 1;
+        for highway in self.highways.iter_mut() {
+            highway.clip(rectangle);
+            // The chunks hold fewer of its cells
+            for chunk in self.index.keys() {
+                highway.hide_chunk(*chunk, Self::chunk_rectangle(*chunk));
+            }
+            highway.set_version(self.version);
+        }
+        self.highways
+            .retain(|highway| highway.get_bounds().is_some());
+    }
+
+    // Extend a highway by up to `steps` steps, as long as the ant would really take them.
+    // Periods away from the chunks and the other highways are known from the recorded one
+    // and cost nothing, the others are checked against the grid step by step, `work` steps
+    // at most, and written through to the chunks they cross.
+    // Returns the number of steps added and of steps checked.
+    pub fn grow_highway(&mut self, index: usize, steps: u64, work: u64) -> (u64, u64) {
+        let length = self.highways[index].get_period() as u64;
+        let start = self.highways[index].get_end();
+        let target = start.saturating_add(steps);
+        let mut checked = 0;
+        let mut touched = HashSet::new();
+        loop {
+            let end = self.highways[index].get_end();
+            if end >= target {
+                break;
+            }
+            let period = end / length;
+            if self.is_clear_period(index, period) {
+                let next = self.next_crowded_period(index, period + 1);
+                self.highways[index].set_end(target.min(next.saturating_mul(length)));
+                continue;
+            }
+            if checked >= work {
+                break;
+            }
+
+            let from = (end - period * length) as usize;
+            let to = (target - period * length).min(length) as usize;
+            let stop = self.check_period(index, period, from, to);
+            checked += (to - from) as u64;
+            for step in from..stop {
+                let highway = &self.highways[index];
+                let position = highway.get_cell(period, step);
+                let write = highway.get_step(step).write;
+                let (chunk, _) = Self::split_position(position);
+                if self.index.contains_key(&chunk) {
+                    self.set(position, write);
+                    touched.insert(chunk);
+                }
+            }
+            self.highways[index].set_end(period * length + stop as u64);
+            if stop < to {
+                break;
+            }
+        }
+
+        for chunk in touched {
+            self.highways[index].hide_chunk(chunk, Self::chunk_rectangle(chunk));
+        }
+        self.version += 1;
+        let highway = &mut self.highways[index];
+        highway.refresh();
+        highway.set_version(self.version);
+        (highway.get_end() - start, checked)
+    }
+
+    // Whether a period of a highway is settled and crosses no chunk and no other highway
+    fn is_clear_period(&self, index: usize, period: u64) -> bool {
+        let highway = &self.highways[index];
+        if !highway.is_settled(period) {
+            return false;
+        }
+        let rectangle = highway.get_period_bounds(period);
+        let (min_chunk, _) = Self::split_position((rectangle.0, rectangle.1));
+        let (max_chunk, _) = Self::split_position((rectangle.2, rectangle.3));
+        let region_chunks = (max_chunk.0 as i64 - min_chunk.0 as i64 + 1)
+            * (max_chunk.1 as i64 - min_chunk.1 as i64 + 1);
+        let crosses_chunks = if region_chunks <= self.index.len() as i64 {
+            (min_chunk.1..=max_chunk.1).any(|chunk_y| {
+                (min_chunk.0..=max_chunk.0)
+                    .any(|chunk_x| self.index.contains_key(&(chunk_x, chunk_y)))
+            })
+        } else {
+            self.index.keys().any(|(chunk_x, chunk_y)| {
+                *chunk_x >= min_chunk.0
+                    && *chunk_x <= max_chunk.0
+                    && *chunk_y >= min_chunk.1
+                    && *chunk_y <= max_chunk.1
+            })
+        };
+        !crosses_chunks
+            && !self
+                .other_highways(index)
+                .any(|bounds| overlaps(bounds, rectangle))
+    }
+
+    // First period of a highway from `from` on that crosses a chunk or another highway
+    fn next_crowded_period(&self, index: usize, from: u64) -> u64 {
+        let highway = &self.highways[index];
+        self.index
+            .keys()
+            .map(|chunk| Self::chunk_rectangle(*chunk))
+            .chain(self.other_highways(index))
+            .filter_map(|rectangle| highway.periods_touching(rectangle))
+            .filter(|(_, last)| *last >= from)
+            .map(|(first, _)| first.max(from))
+            .min()
+            .unwrap_or(u64::MAX)
+    }
+
+    fn other_highways(&self, index: usize) -> impl Iterator<Item = (i32, i32, i32, i32)> + '_ {
+        self.highways
+            .iter()
+            .enumerate()
+            .filter(move |(other, _)| *other != index)
+            .filter_map(|(_, highway)| highway.get_bounds())
+    }
+
+    // First step of a period, between `from` and `to`, that would not find the color the
+    // recorded period read on a cell it visits first, `to` when every step does
+    fn check_period(&self, index: usize, period: u64, from: usize, to: usize) -> usize {
+        let highway = &self.highways[index];
+        for step in from..to {
+            if !highway.is_first_visit(step) {
+                continue;
+            }
+            let position = highway.get_cell(period, step);
+            let crossed = self
+                .highways
+                .iter()
+                .enumerate()
+                .any(|(other, highway)| other != index && highway.get(position).is_some());
+            if crossed || self.get(position) != highway.get_step(step).read {
+                return step;
+            }
+        }
+        to
+    }
+}
+
+// State highways give a cell outside the chunks
+fn highway_value(highways: &[Highway], position: (i32, i32)) -> u8 {
+    highways
+        .iter()
+        .find_map(|highway| highway.get(position))
+        .unwrap_or(0)
+}
+
+impl Default for ChunkedGrid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Read-only view of a grid with its own lookup cache, each thread reading the grid clones one
+#[derive(Clone)]
+pub struct GridReader<'a> {
+    chunks: &'a [Chunk],
+    index: &'a HashMap<(i32, i32), usize>,
+    highways: &'a [Highway],
+    last_lookup: ((i32, i32), usize),
+}
+
+impl GridReader<'_> {
+    pub fn get(&mut self, position: (i32, i32)) -> u8 {
+        let (chunk, offset) = ChunkedGrid::split_position(position);
+        let (last_chunk, last_index) = self.last_lookup;
+        let index = if last_index != NO_CHUNK && last_chunk == chunk {
+            last_index
+        } else {
+            match self.index.get(&chunk) {
+                Some(index) => {
+                    self.last_lookup = (chunk, *index);
+                    *index
+                }
+                None => return highway_value(self.highways, position),
+            }
+        };
+        self.chunks[index].cells[offset]
+    }
+}
@@ -0,0 +1,684 @@
+//! Highways kept as a pattern instead of cells.
+//!
+//! A lone ant on a highway repeats the same period of steps, shifted by the same displacement
+//! every time. A [`Highway`] keeps one recorded period and the range of steps the ant took
+//! after it, which describes every cell the ant left behind whatever the number of periods.
+//! A cell holds the color written by the last step on it. The steps reaching a cell all visit
+//! the same line parallel to the displacement, so cells are looked up and counted line by line.
+
+use std::collections::HashMap;
+
+/// One step of the recorded period
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct HighwayStep {
+    // Position of the ant relative to where the period started
+    pub offset: (i32, i32),
+    // Heading and internal state of the ant before the step
+    pub heading: u8,
+    pub state: u8,
+    // Color read from the cell and written back
+    pub read: u8,
+    pub write: u8,
+}
+
+/// Cells base + m * displacement, relative to the origin of the highway
+struct Line {
+    base: (i64, i64),
+    // Shift and step of every visit: in period p the step is on the cell of index p + shift
+    visits: Vec<(i64, usize)>,
+    // Visit writing last on a cell, once every visit of the line has reached it
+    last: usize,
+}
+
+/// Steps of a lone ant following a recorded period, by their numbers from the start of it
+pub struct Highway {
+    steps: Vec<HighwayStep>,
+    // Whether a step is the first of the period on its cell
+    first_visits: Vec<bool>,
+    lines: Vec<Line>,
+    line_index: HashMap<(i64, i64), usize>,
+    // Position the recorded period started from, period p starts p displacements further
+    origin: (i32, i32),
+    displacement: (i32, i32),
+    // Bounding box (min_x, min_y, max_x, max_y) of the offsets of the period
+    extent: (i32, i32, i32, i32),
+    // First period from which every color read is one the period itself wrote, or the blank
+    // background. None when the period does not explain what it reads.
+    settled: Option<u64>,
+    // Steps covered, the recorded period is left to the grid
+    first: u64,
+    end: u64,
+    // Rectangle the covered cells are cut to, a clipped highway does not grow anymore
+    clip: Option<(i32, i32, i32, i32)>,
+    // Number of covered cells in each state, and of those lying in the chunks the grid
+    // allocated, which hold their own copy of these cells
+    counts: Vec<u64>,
+    hidden: HashMap<(i32, i32), Vec<u64>>,
+    hidden_counts: Vec<u64>,
+    // Bounding boxes of the covered cells and of the painted ones
+    bounds: Option<(i32, i32, i32, i32)>,
+    painted_bounds: Option<(i32, i32, i32, i32)>,
+    // Grid version of the last change
+    version: u64,
+}
+
+impl Highway {
+    // Highway of an ant that stepped through a period from the origin and ended up one
+    // displacement further with its heading and state unchanged. It covers no step yet.
+    pub fn new(origin: (i32, i32), displacement: (i32, i32), steps: Vec<HighwayStep>) -> Self {
+        let length = steps.len();
+        let mut last_visits: HashMap<(i32, i32), usize> = HashMap::new();
+        let first_visits: Vec<bool> = steps
+            .iter()
+            .enumerate()
+            .map(|(index, step)| last_visits.insert(step.offset, index).is_none())
+            .collect();
+        let extent = steps.iter().fold(
+            (i32::MAX, i32::MAX, i32::MIN, i32::MIN),
+            |(min_x, min_y, max_x, max_y), step| {
+                let (x, y) = step.offset;
+                (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+            },
+        );
+
+        let mut lines: Vec<Line> = Vec::new();
+        let mut line_index = HashMap::new();
+        for (index, step) in steps.iter().enumerate() {
+            let offset = (step.offset.0 as i64, step.offset.1 as i64);
+            let (base, shift) = split_line(offset, displacement);
+            let line = *line_index.entry(base).or_insert_with(|| {
+                lines.push(Line {
+                    base,
+                    visits: Vec::new(),
+                    last: 0,
+                });
+                lines.len() - 1
+            });
+            lines[line].visits.push((shift, index));
+        }
+        for line in lines.iter_mut() {
+            line.last = (0..line.visits.len())
+                .max_by_key(|visit| {
+                    let (shift, step) = line.visits[*visit];
+                    step as i64 - shift * length as i64
+                })
+                .unwrap_or(0);
+        }
+
+        // The first visit of a cell in period p reads what the period k displacements behind
+        // left there, k being the smallest that visits the cell, or the background when none
+        // does. From period k on, that period is part of the highway or the recorded one.
+        let inside = |(x, y): (i64, i64)| {
+            x >= extent.0 as i64
+                && y >= extent.1 as i64
+                && x <= extent.2 as i64
+                && y <= extent.3 as i64
+        };
+        let mut settled = Some(1);
+        for (step, _) in steps
+            .iter()
+            .zip(&first_visits)
+            .filter(|(_, first_visit)| **first_visit)
+        {
+            let mut behind = 1;
+            let mut writer = None;
+            loop {
+                let cell = (
+                    step.offset.0 as i64 + behind * displacement.0 as i64,
+                    step.offset.1 as i64 + behind * displacement.1 as i64,
+                );
+                if !inside(cell) {
+                    break;
+                }
+                if let Some(index) = last_visits.get(&(cell.0 as i32, cell.1 as i32)) {
+                    writer = Some(*index);
+                    break;
+                }
+                behind += 1;
+            }
+            let expected = writer.map_or(0, |index| steps[index].write);
+            if step.read != expected {
+                settled = None;
+                break;
+            }
+            settled = settled.map(|settled: u64| settled.max(behind as u64));
+        }
+
+        let states = steps.iter().map(|step| step.write).max().unwrap_or(0) as usize + 1;
+        let mut highway = Highway {
+            steps,
+            first_visits,
+            lines,
+            line_index,
+            origin,
+            displacement,
+            extent,
+            settled,
+            first: length as u64,
+            end: length as u64,
+            clip: None,
+            counts: vec![0; states],
+            hidden: HashMap::new(),
+            hidden_counts: vec![0; states],
+            bounds: None,
+            painted_bounds: None,
+            version: 0,
+        };
+        highway.refresh();
+        highway
+    }
+
+    // Number of steps in a period
+    pub fn get_period(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn get_displacement(&self) -> (i32, i32) {
+        self.displacement
+    }
+
+    pub fn get_step(&self, index: usize) -> &HighwayStep {
+        &self.steps[index]
+    }
+
+    pub fn is_first_visit(&self, index: usize) -> bool {
+        self.first_visits[index]
+    }
+
+    // Whether the cells of a period are known without looking at the grid, provided nothing
+    // but the highway ever wrote around them
+    pub fn is_settled(&self, period: u64) -> bool {
+        self.settled.is_some_and(|settled| period >= settled)
+    }
+
+    // Number of the step after the last one covered
+    pub fn get_end(&self) -> u64 {
+        self.end
+    }
+
+    pub fn set_end(&mut self, end: u64) {
+        self.end = end.max(self.first);
+        self.bounds = self.covered_bounds();
+    }
+
+    // Forget the cells outside an inclusive rectangle
+    pub fn clip(&mut self, rectangle: (i32, i32, i32, i32)) {
+        self.clip = Some(match self.clip {
+            Some(clip) => intersection(clip, rectangle).unwrap_or((0, 0, -1, -1)),
+            None => rectangle,
+        });
+        self.refresh();
+    }
+
+    pub fn is_clipped(&self) -> bool {
+        self.clip.is_some()
+    }
+
+    pub fn get_version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn set_version(&mut self, version: u64) {
+        self.version = version;
+    }
+
+    // Cell of a step of the recorded period, in a later period
+    pub fn get_cell(&self, period: u64, index: usize) -> (i32, i32) {
+        let offset = self.steps[index].offset;
+        (
+            (self.origin.0 as i64 + offset.0 as i64 + period as i64 * self.displacement.0 as i64)
+                as i32,
+            (self.origin.1 as i64 + offset.1 as i64 + period as i64 * self.displacement.1 as i64)
+                as i32,
+        )
+    }
+
+    // Position, heading and state of the ant before a step
+    pub fn get_pose(&self, step: u64) -> ((i32, i32), u8, u8) {
+        let length = self.steps.len() as u64;
+        let index = (step % length) as usize;
+        let HighwayStep { heading, state, .. } = self.steps[index];
+        (self.get_cell(step / length, index), heading, state)
+    }
+
+    // Bounding box of the cells of a period
+    pub fn get_period_bounds(&self, period: u64) -> (i32, i32, i32, i32) {
+        let (min_x, min_y, max_x, max_y) = self.extent;
+        let shift = |origin: i32, value: i32, displacement: i32| {
+            (origin as i64 + value as i64 + period as i64 * displacement as i64) as i32
+        };
+        (
+            shift(self.origin.0, min_x, self.displacement.0),
+            shift(self.origin.1, min_y, self.displacement.1),
+            shift(self.origin.0, max_x, self.displacement.0),
+            shift(self.origin.1, max_y, self.displacement.1),
+        )
+    }
+
+    // Bounding box of the covered cells, None while no step is covered
+    pub fn get_bounds(&self) -> Option<(i32, i32, i32, i32)> {
+        self.bounds
+    }
+
+    // Bounding box of the covered cells with a non-zero state
+    pub fn get_painted_bounds(&self) -> Option<(i32, i32, i32, i32)> {
+        self.painted_bounds
+    }
+
+    // Range of periods whose bounding box meets an inclusive rectangle, not limited to the
+    // covered ones
+    pub fn periods_touching(&self, rectangle: (i32, i32, i32, i32)) -> Option<(u64, u64)> {
+        let (min_x, min_y, max_x, max_y) = self.extent;
+        let axis = |low: i32, high: i32, origin: i32, min: i32, max: i32, displacement: i32| {
+            multiples_between(
+                low as i64 - origin as i64 - max as i64,
+                high as i64 - origin as i64 - min as i64,
+                0,
+                displacement as i64,
+            )
+        };
+        let xs = axis(
+            rectangle.0,
+            rectangle.2,
+            self.origin.0,
+            min_x,
+            max_x,
+            self.displacement.0,
+        )?;
+        let ys = axis(
+            rectangle.1,
+            rectangle.3,
+            self.origin.1,
+            min_y,
+            max_y,
+            self.displacement.1,
+        )?;
+        let low = xs.0.max(ys.0).max(0);
+        let high = xs.1.min(ys.1);
+        (low <= high).then_some((low as u64, high as u64))
+    }
+
+    // State of a covered cell, None for the cells the highway does not cover
+    pub fn get(&self, position: (i32, i32)) -> Option<u8> {
+        let (min_x, min_y, max_x, max_y) = self.bounds?;
+        let (x, y) = position;
+        if x < min_x || y < min_y || x > max_x || y > max_y {
+            return None;
+        }
+        let relative = (
+            x as i64 - self.origin.0 as i64,
+            y as i64 - self.origin.1 as i64,
+        );
+        let (base, index) = split_line(relative, self.displacement);
+        let line = &self.lines[*self.line_index.get(&base)?];
+        self.line_value(line, index)
+    }
+
+    // Every covered cell inside an inclusive rectangle with its state, background included
+    pub fn cells_in(
+        &self,
+        rectangle: (i32, i32, i32, i32),
+    ) -> impl Iterator<Item = ((i32, i32), u8)> + '_ {
+        let rectangle = self
+            .bounds
+            .and_then(|bounds| intersection(bounds, rectangle));
+        let lines = if rectangle.is_some() {
+            &self.lines[..]
+        } else {
+            &[]
+        };
+        let (dx, dy) = (self.displacement.0 as i64, self.displacement.1 as i64);
+        lines.iter().flat_map(move |line| {
+            let span = rectangle.and_then(|rectangle| self.line_span(line, rectangle));
+            span.into_iter()
+                .flat_map(move |(start, low, settled, high)| {
+                    (low..=high).filter_map(move |index| {
+                        let state = if index <= settled {
+                            self.steps[line.visits[line.last].1].write
+                        } else {
+                            self.line_value(line, index)?
+                        };
+                        let position =
+                            ((start.0 + index * dx) as i32, (start.1 + index * dy) as i32);
+                        Some((position, state))
+                    })
+                })
+        })
+    }
+
+    // Every covered cell with its state
+    pub fn cells(&self) -> impl Iterator<Item = ((i32, i32), u8)> + '_ {
+        self.cells_in(self.bounds.unwrap_or((0, 0, -1, -1)))
+    }
+
+    // Number of covered cells with a non-zero state, outside the chunks allocated by the grid
+    pub fn get_painted(&self) -> u64 {
+        self.get_counts().skip(1).sum()
+    }
+
+    // Number of covered cells in each state, outside the chunks allocated by the grid
+    pub fn get_counts(&self) -> impl Iterator<Item = u64> + '_ {
+        self.counts
+            .iter()
+            .zip(&self.hidden_counts)
+            .map(|(count, hidden)| count - hidden)
+    }
+
+    // Take the covered cells of a chunk the grid allocates out of the counts, returning them
+    // with their states
+    pub fn hide_chunk(
+        &mut self,
+        chunk: (i32, i32),
+        rectangle: (i32, i32, i32, i32),
+    ) -> Vec<((i32, i32), u8)> {
+        let cells: Vec<_> = self.cells_in(rectangle).collect();
+        let mut counts = vec![0; self.counts.len()];
+        for (_, state) in &cells {
+            counts[*state as usize] += 1;
+        }
+        if let Some(previous) = self.hidden.remove(&chunk) {
+            for (hidden, count) in self.hidden_counts.iter_mut().zip(previous) {
+                *hidden -= count;
+            }
+        }
+        if !cells.is_empty() {
+            for (hidden, count) in self.hidden_counts.iter_mut().zip(&counts) {
+                *hidden += count;
+            }
+            self.hidden.insert(chunk, counts);
+        }
+        cells
+    }
+
+    // Count the covered cells again after the end moved
+    pub fn refresh(&mut self) {
+        self.bounds = self.covered_bounds();
+        let mut counts = vec![0; self.counts.len()];
+        let mut painted: Option<(i32, i32, i32, i32)> = None;
+        let (dx, dy) = (self.displacement.0 as i64, self.displacement.1 as i64);
+        for line in &self.lines {
+            let Some((_, low, settled, high)) =
+                self.bounds.and_then(|bounds| self.line_span(line, bounds))
+            else {
+                continue;
+            };
+            let last = self.steps[line.visits[line.last].1].write;
+            let mut painted_indices: Option<(i64, i64)> = None;
+            if low <= settled.min(high) {
+                counts[last as usize] += (settled.min(high) - low + 1) as u64;
+                if last != 0 {
+                    painted_indices = Some((low, settled.min(high)));
+                }
+            }
+            for index in low.max(settled + 1)..=high {
+                if let Some(state) = self.line_value(line, index) {
+                    counts[state as usize] += 1;
+                    if state != 0 {
+                        painted_indices = Some(match painted_indices {
+                            Some((first, last)) => (first.min(index), last.max(index)),
+                            None => (index, index),
+                        });
+                    }
+                }
+            }
+            // Cells of a line are in a row, its ends are enough
+            if let Some((first, last)) = painted_indices {
+                for index in [first, last] {
+                    let x = (self.origin.0 as i64 + line.base.0 + index * dx) as i32;
+                    let y = (self.origin.1 as i64 + line.base.1 + index * dy) as i32;
+                    painted = Some(match painted {
+                        Some((min_x, min_y, max_x, max_y)) => {
+                            (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+                        }
+                        None => (x, y, x, y),
+                    });
+                }
+            }
+        }
+        self.counts = counts;
+        self.painted_bounds = painted;
+    }
+
+    fn covered_bounds(&self) -> Option<(i32, i32, i32, i32)> {
+        if self.end <= self.first {
+            return None;
+        }
+        let clip = self
+            .clip
+            .unwrap_or((i32::MIN, i32::MIN, i32::MAX, i32::MAX));
+        let length = self.steps.len() as u64;
+        let (first_x, first_y, first_max_x, first_max_y) =
+            self.get_period_bounds(self.first / length);
+        let (last_x, last_y, last_max_x, last_max_y) =
+            self.get_period_bounds((self.end - 1) / length);
+        let bounds = (
+            first_x.min(last_x),
+            first_y.min(last_y),
+            first_max_x.max(last_max_x),
+            first_max_y.max(last_max_y),
+        );
+        intersection(bounds, clip)
+    }
+
+    // First cell of a line and the indices of its covered cells inside an inclusive rectangle:
+    // the first one, the last one written by the last visit, and the last one
+    fn line_span(
+        &self,
+        line: &Line,
+        rectangle: (i32, i32, i32, i32),
+    ) -> Option<((i64, i64), i64, i64, i64)> {
+        let (low, settled, high) = self.line_range(line)?;
+        let start = (
+            self.origin.0 as i64 + line.base.0,
+            self.origin.1 as i64 + line.base.1,
+        );
+        let (min_x, min_y, max_x, max_y) = rectangle;
+        let xs = multiples_between(
+            min_x as i64,
+            max_x as i64,
+            start.0,
+            self.displacement.0 as i64,
+        )?;
+        let ys = multiples_between(
+            min_y as i64,
+            max_y as i64,
+            start.1,
+            self.displacement.1 as i64,
+        )?;
+        Some((
+            start,
+            low.max(xs.0).max(ys.0),
+            settled,
+            high.min(xs.1).min(ys.1),
+        ))
+    }
+
+    // Indices of the covered cells of a line: the first one, the last one written by the
+    // last visit, and the last one
+    fn line_range(&self, line: &Line) -> Option<(i64, i64, i64)> {
+        if self.end <= self.first {
+            return None;
+        }
+        let length = self.steps.len() as i64;
+        let (first, end) = (self.first as i64, self.end as i64);
+        let (shift, step) = line.visits[line.last];
+        let low = shift + ceil_div(first - step as i64, length);
+        let settled = shift + floor_div(end - 1 - step as i64, length);
+        let high = line
+            .visits
+            .iter()
+            .map(|(shift, step)| shift + floor_div(end - 1 - *step as i64, length))
+            .max()?;
+        Some((low, settled, high))
+    }
+
+    // State of the cell of a line, written by the latest covered step on it
+    fn line_value(&self, line: &Line, index: i64) -> Option<u8> {
+        let length = self.steps.len() as i64;
+        let (first, end) = (self.first as i64, self.end as i64);
+        let step_number = |(shift, step): (i64, usize)| (index - shift) * length + step as i64;
+        // The last visit writes last whenever it is covered
+        let last = step_number(line.visits[line.last]);
+        if last < first {
+            return None;
+        }
+        if last < end {
+            return Some(self.steps[line.visits[line.last].1].write);
+        }
+        line.visits
+            .iter()
+            .map(|visit| (step_number(*visit), visit.1))
+            .filter(|(number, _)| *number >= first && *number < end)
+            .max_by_key(|(number, _)| *number)
+            .map(|(_, step)| self.steps[step].write)
+    }
+}
+
+// Split a position into the first cell of its line along the displacement and its index on it
+fn split_line(position: (i64, i64), displacement: (i32, i32)) -> ((i64, i64), i64) {
+    let (dx, dy) = (displacement.0 as i64, displacement.1 as i64);
+    let index = if dx != 0 {
+        floor_div(position.0, dx)
+    } else {
+        floor_div(position.1, dy)
+    };
+    ((position.0 - index * dx, position.1 - index * dy), index)
+}
+
+// Quotient rounded down, for a divisor of any sign
+fn floor_div(value: i64, divisor: i64) -> i64 {
+    if divisor < 0 {
+        (-value).div_euclid(-divisor)
+    } else {
+        value.div_euclid(divisor)
+    }
+}
+
+fn ceil_div(value: i64, divisor: i64) -> i64 {
+    -floor_div(-value, divisor)
+}
+
+// Inclusive range of m with low <= start + m * step <= high, None when empty
+fn multiples_between(low: i64, high: i64, start: i64, step: i64) -> Option<(i64, i64)> {
+    let (first, last) = match step.signum() {
+        0 => return (low <= start && start <= high).then_some((i64::MIN, i64::MAX)),
+        1 => (ceil_div(low - start, step), floor_div(high - start, step)),
+        _ => (ceil_div(high - start, step), floor_div(low - start, step)),
+    };
+    (first <= last).then_some((first, last))
+}
+
+// Cells two inclusive rectangles (min_x, min_y, max_x, max_y) share, None when there are none
+fn intersection(
+    first: (i32, i32, i32, i32),
+    second: (i32, i32, i32, i32),
+) -> Option<(i32, i32, i32, i32)> {
+    let shared = (
+        first.0.max(second.0),
+        first.1.max(second.1),
+        first.2.min(second.2),
+        first.3.min(second.3),
+    );
+    (shared.0 <= shared.2 && shared.1 <= shared.3).then_some(shared)
+}
+
+// Whether two inclusive rectangles (min_x, min_y, max_x, max_y) share a cell
+pub fn overlaps(first: (i32, i32, i32, i32), second: (i32, i32, i32, i32)) -> bool {
+    first.0 <= second.2 && second.0 <= first.2 && first.1 <= second.3 && second.1 <= first.3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Steps of a period overlapping itself and the periods around it
+    fn period() -> Vec<HighwayStep> {
+        [
+            ((0, 0), 1),
+            ((1, 0), 2),
+            ((1, 1), 0),
+            ((3, 1), 1),
+            ((1, 0), 1),
+            ((-2, 2), 2),
+        ]
+        .into_iter()
+        .map(|(offset, write)| HighwayStep {
+            offset,
+            heading: 0,
+            state: 0,
+            read: 0,
+            write,
+        })
+        .collect()
+    }
+
+    #[test]
+    fn cells_are_the_last_write_of_the_covered_steps() {
+        let mut highway = Highway::new((5, -3), (2, 1), period());
+        for end in [7, 11, 12, 20, 41, 100] {
+            highway.set_end(end);
+            highway.refresh();
+            let mut expected = HashMap::new();
+            for step in 6..end {
+                let (position, _, _) = highway.get_pose(step);
+                expected.insert(position, highway.get_step((step % 6) as usize).write);
+            }
+
+            let (min_x, min_y, max_x, max_y) = highway.get_bounds().unwrap();
+            for x in min_x - 2..=max_x + 2 {
+                for y in min_y - 2..=max_y + 2 {
+                    assert_eq!(highway.get((x, y)), expected.get(&(x, y)).copied());
+                }
+            }
+            let mut cells: Vec<_> = highway.cells().collect();
+            cells.sort();
+            let mut all: Vec<_> = expected
+                .iter()
+                .map(|(cell, state)| (*cell, *state))
+                .collect();
+            all.sort();
+            assert_eq!(cells, all);
+
+            let mut counts = vec![0; 3];
+            for state in expected.values() {
+                counts[*state as usize] += 1;
+            }
+            assert_eq!(highway.get_counts().collect::<Vec<_>>(), counts);
+            let painted = expected.iter().filter(|(_, state)| **state != 0).fold(
+                None,
+                |bounds, ((x, y), _)| match bounds {
+                    None => Some((*x, *y, *x, *y)),
+                    Some((min_x, min_y, max_x, max_y)) => {
+                        Some((min_x.min(*x), min_y.min(*y), max_x.max(*x), max_y.max(*y)))
+                    }
+                },
+            );
+            assert_eq!(highway.get_painted_bounds(), painted);
+
+            let rectangle = (min_x + 3, min_y + 1, max_x - 4, max_y);
+            let mut inside: Vec<_> = highway.cells_in(rectangle).collect();
+            inside.sort();
+            all.retain(|(cell, _)| overlaps((cell.0, cell.1, cell.0, cell.1), rectangle));
+            assert_eq!(inside, all);
+        }
+    }
+
+    #[test]
+    fn hidden_chunks_leave_the_counts() {
+        let mut highway = Highway::new((0, 0), (2, 1), period());
+        highway.set_end(60);
+        highway.refresh();
+        let total: u64 = highway.get_counts().sum();
+        let hidden = highway.hide_chunk((0, 0), (0, 0, 9, 9));
+        assert!(!hidden.is_empty());
+        assert_eq!(
+            highway.get_counts().sum::<u64>(),
+            total - hidden.len() as u64
+        );
+        highway.hide_chunk((0, 0), (0, 0, 9, 9));
+        assert_eq!(
+            highway.get_counts().sum::<u64>(),
+            total - hidden.len() as u64
+        );
+    }
+}
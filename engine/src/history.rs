@@ -0,0 +1,392 @@
+//! Undo and redo of a [`Gamestate`], stored as the differences between its successive states.
+//!
+//! The gamestate journals what it does while a journal is open, the history keeps the
+//! closed journals. Consecutive simulation chunks are merged into a single change.
+//! Simulation chunks are only journaled on request. After a simulation run that was not
+//! journaled, the edits made before it are still undone against the state the run left.
+
+use crate::gamemodes::langton::{Ant, Gamestate};
+use crate::world::World;
+use std::collections::{HashMap, VecDeque};
+
+// Cells and ants kept over every stored change, the oldest changes are forgotten past it
+pub const MAX_HISTORY_SIZE: usize = 1 << 22;
+
+/// Ants taken out of the list of ants or appended to it. The order of the ants decides which
+/// of several ants on a cell writes, so they are put back exactly where they were.
+pub enum AntChange {
+    // Ants with the index they had in the list, in ascending order
+    Removed(Vec<(usize, Ant)>),
+    // Ants appended at the end of the list
+    Added(Vec<Ant>),
+}
+
+impl AntChange {
+    // Bring the list back to before the ants were removed or added
+    fn undo(&self, ants: &mut Vec<Ant>) {
+        match self {
+            AntChange::Removed(removed) => {
+                let mut kept = std::mem::take(ants).into_iter();
+                let mut removed = removed.iter().peekable();
+                while let Some(ant) = removed
+                    .next_if(|(index, _)| *index <= ants.len())
+                    .map(|(_, ant)| ant.clone())
+                    .or_else(|| kept.next())
+                {
+                    ants.push(ant);
+                }
+                // Ants died since, the indices past the end of the list go at the end
+                ants.extend(removed.map(|(_, ant)| ant.clone()));
+            }
+            AntChange::Added(added) => ants.truncate(ants.len().saturating_sub(added.len())),
+        }
+    }
+
+    fn redo(&self, ants: &mut Vec<Ant>) {
+        match self {
+            AntChange::Removed(removed) => {
+                let mut removed = removed.iter().map(|(index, _)| *index).peekable();
+                let mut index = 0;
+                ants.retain(|_| {
+                    let taken = removed.next_if_eq(&index).is_some();
+                    index += 1;
+                    !taken
+                });
+            }
+            AntChange::Added(added) => ants.extend(added.iter().cloned()),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            AntChange::Removed(removed) => removed.len(),
+            AntChange::Added(added) => added.len(),
+        }
+    }
+}
+
+/// Difference between two states of a gamestate
+#[derive(Default)]
+pub struct Change {
+    // State of every written cell before and after the change
+    pub cells: HashMap<(i32, i32), (u8, u8)>,
+    // Ants removed and added, in the order it happened
+    pub ants: Vec<AntChange>,
+    // Selected rule before and after
+    pub rule: Option<(usize, usize)>,
+    // World before and after, None inside for the unbounded grid
+    pub world: Option<(Option<World>, Option<World>)>,
+    pub iteration: Option<(u128, u128)>,
+}
+
+impl Change {
+    pub fn record_cell(&mut self, position: (i32, i32), before: u8, after: u8) {
+        self.cells
+            .entry(position)
+            .and_modify(|(_, last)| *last = after)
+            .or_insert((before, after));
+    }
+
+    pub fn record_removed_ants(&mut self, ants: Vec<(usize, Ant)>) {
+        if !ants.is_empty() {
+            self.ants.push(AntChange::Removed(ants));
+        }
+    }
+
+    pub fn record_added_ants(&mut self, ants: &[Ant]) {
+        if ants.is_empty() {
+            return;
+        }
+        match self.ants.last_mut() {
+            Some(AntChange::Added(added)) => added.extend(ants.iter().cloned()),
+            _ => self.ants.push(AntChange::Added(ants.to_vec())),
+        }
+    }
+
+    // Put the ants back as they were before the change
+    pub fn undo_ants(&self, ants: &mut Vec<Ant>) {
+        for change in self.ants.iter().rev() {
+            change.undo(ants);
+        }
+    }
+
+    pub fn redo_ants(&self, ants: &mut Vec<Ant>) {
+        for change in &self.ants {
+            change.redo(ants);
+        }
+    }
+
+    pub fn record_rule(&mut self, before: usize, after: usize) {
+        let first = self.rule.map_or(before, |(first, _)| first);
+        self.rule = Some((first, after));
+    }
+
+    pub fn record_world(&mut self, before: Option<World>, after: Option<World>) {
+        let first = self.world.map_or(before, |(first, _)| first);
+        self.world = Some((first, after));
+    }
+
+    pub fn record_iteration(&mut self, before: u128, after: u128) {
+        let first = self.iteration.map_or(before, |(first, _)| first);
+        self.iteration = Some((first, after));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+            && self.ants.is_empty()
+            && self.rule.is_none()
+            && self.world.is_none()
+            && self.iteration.is_none()
+    }
+
+    // Number of cells and ants held
+    pub fn get_size(&self) -> usize {
+        self.cells.len() + self.ants.iter().map(AntChange::len).sum::<usize>()
+    }
+
+    // Append a simulation chunk that directly followed this one.
+    // Simulation chunks replace every ant, so the ants added by this chunk are those the next removes.
+    fn merge_simulation(&mut self, next: Change) {
+        for (position, (before, after)) in next.cells {
+            self.record_cell(position, before, after);
+        }
+        if matches!(self.ants.last(), Some(AntChange::Added(_))) {
+            self.ants.pop();
+        }
+        self.ants.extend(
+            next.ants
+                .into_iter()
+                .filter(|change| matches!(change, AntChange::Added(_))),
+        );
+        if let Some((before, after)) = next.iteration {
+            self.record_iteration(before, after);
+        }
+    }
+}
+
+/// What a change comes from, simulation chunks following each other are undone together
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ChangeKind {
+    Edit,
+    Simulation,
+}
+
+pub struct History {
+    undo: VecDeque<(ChangeKind, Change)>,
+    redo: Vec<(ChangeKind, Change)>,
+    // Cells and ants held by the undo and redo stacks
+    size: usize,
+    // Simulation chunks are journaled too, at the cost of every cell written and a copy of every ant
+    simulation: bool,
+    // The last simulation run outgrew the history, it is not journaled until the next edit
+    overflowed: bool,
+}
+
+impl History {
+    pub fn new() -> Self {
+        History {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            size: 0,
+            simulation: false,
+            overflowed: false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.size = 0;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn get_simulation_recording(&self) -> bool {
+        self.simulation
+    }
+
+    pub fn set_simulation_recording(&mut self, value: bool) {
+        self.simulation = value;
+    }
+
+    // Whether changes of a kind are journaled. After a simulation run that is not journaled,
+    // forget_simulation should be called.
+    pub fn is_journaling(&self, kind: ChangeKind) -> bool {
+        kind == ChangeKind::Edit || (self.simulation && !self.overflowed)
+    }
+
+    // Drop what a simulation run that was not journaled made stale: the simulation chunks,
+    // which would bring back every ant as it was, and the undone changes. The edits stay and
+    // are undone against the state the run left.
+    pub fn forget_simulation(&mut self) {
+        self.redo.clear();
+        self.undo.retain(|(kind, _)| *kind == ChangeKind::Edit);
+        self.size = self.undo.iter().map(|(_, change)| change.get_size()).sum();
+    }
+
+    // Start journaling what the gamestate does
+    pub fn begin(&self, gamestate: &mut Gamestate, kind: ChangeKind) {
+        if self.is_journaling(kind) {
+            gamestate.open_journal();
+        }
+    }
+
+    // Store what the gamestate did since begin
+    pub fn commit(&mut self, gamestate: &mut Gamestate, kind: ChangeKind) {
+        let Some(change) = gamestate.close_journal() else {
+            return;
+        };
+        if kind == ChangeKind::Edit {
+            self.overflowed = false;
+        }
+        self.size -= self
+            .redo
+            .drain(..)
+            .map(|(_, change)| change.get_size())
+            .sum::<usize>();
+
+        match self.undo.back_mut() {
+            Some((ChangeKind::Simulation, last)) if kind == ChangeKind::Simulation => {
+                self.size -= last.get_size();
+                last.merge_simulation(change);
+                self.size += last.get_size();
+            }
+            _ => {
+                self.size += change.get_size();
+                self.undo.push_back((kind, change));
+            }
+        }
+
+        // A change larger than the whole history cannot be undone, nor can anything before it
+        if self
+            .undo
+            .back()
+            .is_some_and(|(_, change)| change.get_size() > MAX_HISTORY_SIZE)
+        {
+            self.clear();
+            self.overflowed = kind == ChangeKind::Simulation;
+            return;
+        }
+        while self.size > MAX_HISTORY_SIZE {
+            if let Some((_, oldest)) = self.undo.pop_front() {
+                self.size -= oldest.get_size();
+            }
+        }
+    }
+
+    // Bring the gamestate back to before the last change, returning false when there is none
+    pub fn undo(&mut self, gamestate: &mut Gamestate) -> bool {
+        let Some((kind, change)) = self.undo.pop_back() else {
+            return false;
+        };
+        gamestate.undo_change(&change);
+        self.redo.push((kind, change));
+        true
+    }
+
+    // Apply the last undone change again, returning false when there is none
+    pub fn redo(&mut self, gamestate: &mut Gamestate) -> bool {
+        let Some((kind, change)) = self.redo.pop() else {
+            return false;
+        };
+        gamestate.redo_change(&change);
+        self.undo.push_back((kind, change));
+        true
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamemodes::langton::Direction;
+
+    fn cells(gamestate: &Gamestate) -> Vec<((i32, i32), u8)> {
+        let mut cells: Vec<_> = gamestate.get_grid().iter().collect();
+        cells.sort();
+        cells
+    }
+
+    fn ants(gamestate: &Gamestate) -> Vec<(i32, i32, u8)> {
+        gamestate
+            .get_ants()
+            .map(|ant| (ant.x, ant.y, ant.heading))
+            .collect()
+    }
+
+    fn two_ants() -> Gamestate {
+        let mut gamestate = Gamestate::new();
+        gamestate.add_ant(Ant::place_ant(0, 0, Direction::Up));
+        gamestate.add_ant(Ant::place_ant(9, 0, Direction::Left));
+        gamestate
+    }
+
+    #[test]
+    fn undo_and_redo_are_exact() {
+        let mut gamestate = two_ants();
+        let mut history = History::new();
+        history.set_simulation_recording(true);
+        let start = (cells(&gamestate), ants(&gamestate));
+
+        history.begin(&mut gamestate, ChangeKind::Edit);
+        assert_eq!(gamestate.remove_ants((9, 0)), 1);
+        history.commit(&mut gamestate, ChangeKind::Edit);
+        history.begin(&mut gamestate, ChangeKind::Simulation);
+        gamestate.step(500);
+        history.commit(&mut gamestate, ChangeKind::Simulation);
+        let end = (cells(&gamestate), ants(&gamestate));
+
+        assert!(history.undo(&mut gamestate));
+        assert!(history.undo(&mut gamestate));
+        assert_eq!((cells(&gamestate), ants(&gamestate)), start);
+        assert_eq!(*gamestate.get_iteration(), 0);
+        assert!(history.redo(&mut gamestate));
+        assert!(history.redo(&mut gamestate));
+        assert_eq!((cells(&gamestate), ants(&gamestate)), end);
+        assert_eq!(*gamestate.get_iteration(), 500);
+    }
+
+    #[test]
+    fn edits_stay_undoable_across_a_run_that_is_not_journaled() {
+        let mut gamestate = two_ants();
+        let mut history = History::new();
+        assert!(!history.is_journaling(ChangeKind::Simulation));
+
+        history.begin(&mut gamestate, ChangeKind::Edit);
+        gamestate.set_grid_value((500, 500), 1);
+        assert_eq!(gamestate.remove_ants((9, 0)), 1);
+        history.commit(&mut gamestate, ChangeKind::Edit);
+
+        history.begin(&mut gamestate, ChangeKind::Simulation);
+        assert_eq!(gamestate.step(200), 200);
+        history.commit(&mut gamestate, ChangeKind::Simulation);
+        history.forget_simulation();
+        let run = cells(&gamestate);
+        let walker = ants(&gamestate)[0];
+
+        // The painted cell and the removed ant come back, what the run did stays
+        assert!(history.undo(&mut gamestate));
+        assert!(!history.can_undo());
+        assert_eq!(gamestate.get_grid_value((500, 500)), 0);
+        assert_eq!(ants(&gamestate), vec![walker, (9, 0, 3)]);
+        assert_eq!(*gamestate.get_iteration(), 200);
+        let mut expected = run.clone();
+        expected.retain(|(position, _)| *position != (500, 500));
+        assert_eq!(cells(&gamestate), expected);
+
+        assert!(history.redo(&mut gamestate));
+        assert_eq!(cells(&gamestate), run);
+        assert_eq!(ants(&gamestate), vec![walker]);
+    }
+}
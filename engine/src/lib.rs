@@ -0,0 +1,8 @@
+pub mod explore;
+pub mod gamemodes;
+pub mod grid;
+pub mod highway;
+pub mod history;
+pub mod pattern;
+pub mod save;
+pub mod world;
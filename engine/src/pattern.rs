@@ -0,0 +1,227 @@
+//! Cells and ants copied out of a rectangle of the grid, turned or mirrored and pasted elsewhere.
+
+use crate::gamemodes::Gamemode;
+use crate::gamemodes::langton::{Ant, Lattice};
+
+// Largest number of cells in a copied region
+pub const MAX_PATTERN_CELLS: usize = 1 << 20;
+
+/// Every cell of a region, background included so that pasting replaces what lies underneath,
+/// and the ants standing on them. Positions are relative to the top left corner of the pattern.
+#[derive(Clone)]
+pub struct Pattern {
+    lattice: Lattice,
+    cells: Vec<((i32, i32), u8)>,
+    ants: Vec<Ant>,
+}
+
+impl Pattern {
+    // Copy an inclusive rectangle (min_x, min_y, max_x, max_y) of a gamemode,
+    // None when it holds more than MAX_PATTERN_CELLS cells
+    pub fn copy(gamemode: &dyn Gamemode, region: (i32, i32, i32, i32)) -> Option<Pattern> {
+        let (min_x, min_y, max_x, max_y) = region;
+        let area = (max_x as i64 - min_x as i64 + 1) * (max_y as i64 - min_y as i64 + 1);
+        if area > MAX_PATTERN_CELLS as i64 {
+            return None;
+        }
+
+        let grid = gamemode.get_grid();
+        let cells = (min_y..=max_y)
+            .flat_map(|y| (min_x..=max_x).map(move |x| (x, y)))
+            .map(|(x, y)| ((x - min_x, y - min_y), grid.get((x, y))))
+            .collect();
+        let ants = match gamemode.as_langton() {
+            Some(langton) => langton
+                .get_ants_in_region(min_x, max_x, min_y, max_y)
+                .into_iter()
+                .map(|ant| Ant {
+                    x: ant.x - min_x,
+                    y: ant.y - min_y,
+                    ..*ant
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Some(Pattern {
+            lattice: gamemode.get_lattice(),
+            cells,
+            ants,
+        })
+    }
+
+    pub fn get_lattice(&self) -> Lattice {
+        self.lattice
+    }
+
+    pub fn get_cells(&self) -> &[((i32, i32), u8)] {
+        &self.cells
+    }
+
+    pub fn get_ants(&self) -> &[Ant] {
+        &self.ants
+    }
+
+    // Width and height of the rectangle around the pattern, in cells
+    pub fn get_size(&self) -> (i32, i32) {
+        self.cells
+            .iter()
+            .fold((0, 0), |(width, height), ((x, y), _)| {
+                (width.max(x + 1), height.max(y + 1))
+            })
+    }
+
+    // Turn the pattern clockwise by one step of its lattice,
+    // a quarter turn on the square lattice and a sixth of a turn on the hexagonal one
+    pub fn rotate(&mut self) {
+        let degree = self.lattice.get_degree();
+        match self.lattice {
+            Lattice::Square => self.transform(|(x, y)| (-y, x), |heading| (heading + 1) % degree),
+            // Axial coordinates, the third cube coordinate is implied
+            Lattice::Hexagonal => {
+                self.transform(|(q, r)| (-r, q + r), |heading| (heading + 1) % degree)
+            }
+        }
+    }
+
+    // Mirror the pattern left to right
+    pub fn mirror(&mut self) {
+        let degree = self.lattice.get_degree();
+        match self.lattice {
+            Lattice::Square => {
+                self.transform(|(x, y)| (-x, y), |heading| (degree - heading) % degree)
+            }
+            Lattice::Hexagonal => {
+                self.transform(|(q, r)| (-q, q + r), |heading| (degree - heading) % degree)
+            }
+        }
+    }
+
+    fn transform(
+        &mut self,
+        position: impl Fn((i32, i32)) -> (i32, i32),
+        heading: impl Fn(u8) -> u8,
+    ) {
+        for (cell, _) in self.cells.iter_mut() {
+            *cell = position(*cell);
+        }
+        for ant in self.ants.iter_mut() {
+            (ant.x, ant.y) = position((ant.x, ant.y));
+            ant.heading = heading(ant.heading);
+        }
+
+        // Bring the top left corner back to the origin
+        let min_x = self.cells.iter().map(|((x, _), _)| *x).min().unwrap_or(0);
+        let min_y = self.cells.iter().map(|((_, y), _)| *y).min().unwrap_or(0);
+        for ((x, y), _) in self.cells.iter_mut() {
+            (*x, *y) = (*x - min_x, *y - min_y);
+        }
+        for ant in self.ants.iter_mut() {
+            (ant.x, ant.y) = (ant.x - min_x, ant.y - min_y);
+        }
+    }
+
+    // Write the pattern with its top left corner on a position, ants are added to those in place
+    pub fn paste(&self, gamemode: &mut dyn Gamemode, position: (i32, i32)) {
+        for ((x, y), state) in &self.cells {
+            gamemode.set_cell((position.0 + x, position.1 + y), *state);
+        }
+        if let Some(langton) = gamemode.as_langton_mut() {
+            for ant in &self.ants {
+                langton.add_ant(Ant {
+                    x: position.0 + ant.x,
+                    y: position.1 + ant.y,
+                    ..*ant
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamemodes::langton::{Direction, Gamestate};
+
+    // A 3 by 2 region with a painted cell and an ant facing right in its top row
+    fn sample() -> Pattern {
+        let mut gamestate = Gamestate::new();
+        gamestate.set_grid_value((12, 20), 1);
+        gamestate.add_ant(Ant::place_ant(11, 20, Direction::Right));
+        Pattern::copy(&gamestate, (10, 20, 12, 21)).unwrap()
+    }
+
+    fn painted(pattern: &Pattern) -> Vec<(i32, i32)> {
+        pattern
+            .get_cells()
+            .iter()
+            .filter(|(_, state)| *state != 0)
+            .map(|(position, _)| *position)
+            .collect()
+    }
+
+    fn ants(pattern: &Pattern) -> Vec<(i32, i32, u8)> {
+        pattern
+            .get_ants()
+            .iter()
+            .map(|ant| (ant.x, ant.y, ant.heading))
+            .collect()
+    }
+
+    #[test]
+    fn copies_are_relative_to_the_top_left_corner() {
+        let pattern = sample();
+        assert_eq!(pattern.get_size(), (3, 2));
+        assert_eq!(painted(&pattern), vec![(2, 0)]);
+        assert_eq!(ants(&pattern), vec![(1, 0, 1)]);
+    }
+
+    #[test]
+    fn rotation_turns_cells_and_ants_clockwise() {
+        let mut pattern = sample();
+        pattern.rotate();
+        assert_eq!(pattern.get_size(), (2, 3));
+        assert_eq!(painted(&pattern), vec![(1, 2)]);
+        assert_eq!(ants(&pattern), vec![(1, 1, 2)]);
+
+        for _ in 0..3 {
+            pattern.rotate();
+        }
+        assert_eq!(painted(&pattern), painted(&sample()));
+        assert_eq!(ants(&pattern), ants(&sample()));
+    }
+
+    #[test]
+    fn mirroring_flips_left_and_right() {
+        let mut pattern = sample();
+        pattern.mirror();
+        assert_eq!(pattern.get_size(), (3, 2));
+        assert_eq!(painted(&pattern), vec![(0, 0)]);
+        assert_eq!(ants(&pattern), vec![(1, 0, 3)]);
+        pattern.mirror();
+        assert_eq!(ants(&pattern), ants(&sample()));
+    }
+
+    #[test]
+    fn hexagonal_patterns_come_back_after_six_rotations() {
+        let mut pattern = sample();
+        pattern.lattice = Lattice::Hexagonal;
+        let (cells, ants_before) = (painted(&pattern), ants(&pattern));
+        for _ in 0..6 {
+            pattern.rotate();
+        }
+        assert_eq!(painted(&pattern), cells);
+        assert_eq!(ants(&pattern), ants_before);
+    }
+
+    #[test]
+    fn pasting_replaces_the_cells_underneath() {
+        let mut gamestate = Gamestate::new();
+        gamestate.set_grid_value((-5, -5), 2);
+        sample().paste(&mut gamestate, (-7, -5));
+        assert_eq!(gamestate.get_grid_value((-5, -5)), 1);
+        assert_eq!(gamestate.get_grid_value((-7, -5)), 0);
+        let pasted: Vec<_> = gamestate.get_ants().map(|ant| (ant.x, ant.y)).collect();
+        assert_eq!(pasted, vec![(-6, -5)]);
+    }
+}
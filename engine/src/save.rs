@@ -0,0 +1,391 @@
+//! Versioned binary snapshots of a [`Gamestate`].
+//!
+//! Layout (little-endian):
+//! - magic `LANT`, format version (u16)
+//! - iteration (u128), speed (u64), paused (u8)
+//! - world edge (u8, 0 for the unbounded grid, otherwise 1 + wrap 0, wall 1 or kill 2),
+//!   followed for a finite world by its origin x i32, y i32, width u32 and height u32
+//! - rule name and rule notation (u32 length + UTF-8 each), rule colors (u32 count + 0xRRGGBB each)
+//! - cells (u64 count + x i32, y i32, state u8 each)
+//! - ants (u64 count + x i32, y i32, heading u8, internal state u8 each)
+//!
+//! Version 1 had no internal ant state, its ants load in state 0.
+//! Versions 1 and 2 stored the speed as a u32.
+//! Versions 1 to 3 had no world, their ants roam the unbounded grid.
+//! Versions 1 to 4 had no rule name, their rule loads as "Loaded".
+
+use crate::gamemodes::langton::{Ant, Direction, Gamestate, MAX_RULE_STATES, Rule};
+use crate::world::{Edge, World};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"LANT";
+pub const FORMAT_VERSION: u16 = 5;
+// Longest rule name or notation read, anything longer is a corrupted length
+const MAX_TEXT_LENGTH: usize = 1 << 16;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    NotASave,
+    UnsupportedVersion(u16),
+    Corrupted(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "{}", error),
+            SaveError::NotASave => write!(f, "not a Langton's Ant save file"),
+            SaveError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported save version {} (this build reads up to {})",
+                version, FORMAT_VERSION
+            ),
+            SaveError::Corrupted(reason) => write!(f, "corrupted save file: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::UnexpectedEof => SaveError::Corrupted("file ends too early".to_string()),
+            _ => SaveError::Io(error),
+        }
+    }
+}
+
+pub fn save_to_file(gamestate: &Gamestate, path: impl AsRef<Path>) -> Result<(), SaveError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_gamestate(gamestate, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+// Replace the simulation in `gamestate` with the snapshot, leaving it untouched on error
+pub fn load_from_file(gamestate: &mut Gamestate, path: impl AsRef<Path>) -> Result<(), SaveError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    read_gamestate(gamestate, &mut reader)
+}
+
+pub fn write_gamestate(gamestate: &Gamestate, writer: &mut impl Write) -> Result<(), SaveError> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+
+    writer.write_all(&gamestate.get_iteration().to_le_bytes())?;
+    writer.write_all(&gamestate.get_speed().to_le_bytes())?;
+    writer.write_all(&[gamestate.get_pause_state() as u8])?;
+
+    match gamestate.get_world() {
+        None => writer.write_all(&[0])?,
+        Some(world) => {
+            let (x, y) = world.get_origin();
+            let (width, height) = world.get_size();
+            writer.write_all(&[1 + world.get_edge().as_index()])?;
+            writer.write_all(&x.to_le_bytes())?;
+            writer.write_all(&y.to_le_bytes())?;
+            writer.write_all(&width.to_le_bytes())?;
+            writer.write_all(&height.to_le_bytes())?;
+        }
+    }
+
+    let rule = gamestate.get_rule();
+    for text in [rule.get_name(), rule.get_notation()] {
+        writer.write_all(&(text.len() as u32).to_le_bytes())?;
+        writer.write_all(text.as_bytes())?;
+    }
+    writer.write_all(&(rule.get_colors().len() as u32).to_le_bytes())?;
+    for color in rule.get_colors() {
+        writer.write_all(&color.to_le_bytes())?;
+    }
+
+    writer.write_all(&(gamestate.get_grid().len() as u64).to_le_bytes())?;
+    for ((x, y), state) in gamestate.get_grid().iter() {
+        writer.write_all(&x.to_le_bytes())?;
+        writer.write_all(&y.to_le_bytes())?;
+        writer.write_all(&[state])?;
+    }
+
+    writer.write_all(&(gamestate.get_total_ants() as u64).to_le_bytes())?;
+    for ant in gamestate.get_ants() {
+        writer.write_all(&ant.x.to_le_bytes())?;
+        writer.write_all(&ant.y.to_le_bytes())?;
+        writer.write_all(&[ant.heading])?;
+        writer.write_all(&[ant.state])?;
+    }
+    Ok(())
+}
+
+pub fn read_gamestate(gamestate: &mut Gamestate, reader: &mut impl Read) -> Result<(), SaveError> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(SaveError::NotASave);
+    }
+    let version = u16::from_le_bytes(read_array(reader)?);
+    if version == 0 || version > FORMAT_VERSION {
+        return Err(SaveError::UnsupportedVersion(version));
+    }
+
+    let iteration = u128::from_le_bytes(read_array(reader)?);
+    let speed = if version < 3 {
+        u32::from_le_bytes(read_array(reader)?) as u64
+    } else {
+        u64::from_le_bytes(read_array(reader)?)
+    };
+    let paused = read_u8(reader)? != 0;
+    let world = if version < 4 {
+        None
+    } else {
+        read_world(reader)?
+    };
+
+    let name = if version < 5 {
+        "Loaded".to_string()
+    } else {
+        read_text(reader, "rule name")?
+    };
+    let notation = read_text(reader, "rule")?;
+    let color_count = u32::from_le_bytes(read_array(reader)?) as usize;
+    if color_count > MAX_RULE_STATES {
+        return Err(SaveError::Corrupted(format!("{} rule colors", color_count)));
+    }
+    let colors = (0..color_count)
+        .map(|_| Ok(u32::from_le_bytes(read_array(reader)?)))
+        .collect::<Result<Vec<u32>, SaveError>>()?;
+    let rule = Rule::with_colors(&name, &notation, colors)
+        .map_err(|error| SaveError::Corrupted(format!("rule {}: {}", notation, error)))?;
+
+    let cell_count = u64::from_le_bytes(read_array(reader)?);
+    let mut cells = Vec::new();
+    for _ in 0..cell_count {
+        let x = i32::from_le_bytes(read_array(reader)?);
+        let y = i32::from_le_bytes(read_array(reader)?);
+        let state = read_u8(reader)?;
+        if state as usize >= rule.get_length() {
+            return Err(SaveError::Corrupted(format!(
+                "cell ({}, {}) has state {} but the rule only has {}",
+                x,
+                y,
+                state,
+                rule.get_length()
+            )));
+        }
+        if world.is_some_and(|world| !world.contains((x, y))) {
+            return Err(SaveError::Corrupted(format!(
+                "cell ({}, {}) is outside the world",
+                x, y
+            )));
+        }
+        cells.push(((x, y), state));
+    }
+
+    let ant_count = u64::from_le_bytes(read_array(reader)?);
+    let mut ants = Vec::new();
+    for _ in 0..ant_count {
+        let x = i32::from_le_bytes(read_array(reader)?);
+        let y = i32::from_le_bytes(read_array(reader)?);
+        if world.is_some_and(|world| !world.contains((x, y))) {
+            return Err(SaveError::Corrupted(format!(
+                "ant ({}, {}) is outside the world",
+                x, y
+            )));
+        }
+        let heading = read_u8(reader)?;
+        if heading >= rule.get_lattice().get_degree() {
+            return Err(SaveError::Corrupted(format!(
+                "ant ({}, {}) has an invalid heading {}",
+                x, y, heading
+            )));
+        }
+        let mut ant = Ant::place_ant(x, y, Direction::Up);
+        ant.heading = heading;
+        if version >= 2 {
+            ant.state = read_u8(reader)?;
+            if ant.state as usize >= rule.get_table().get_states() {
+                return Err(SaveError::Corrupted(format!(
+                    "ant ({}, {}) is in state {} but the rule only has {}",
+                    x,
+                    y,
+                    ant.state,
+                    rule.get_table().get_states()
+                )));
+            }
+        }
+        ants.push(ant);
+    }
+
+    // Everything was read successfully, apply the snapshot
+    gamestate.reset();
+    select_or_add_rule(gamestate, rule);
+    gamestate.set_iteration(iteration);
+    gamestate.set_speed(speed);
+    gamestate.set_pause_state(paused);
+    gamestate.set_world(world);
+    for (position, state) in cells {
+        gamestate.set_grid_value(position, state);
+    }
+    for ant in ants {
+        gamestate.add_ant(ant);
+    }
+    Ok(())
+}
+
+fn read_world(reader: &mut impl Read) -> Result<Option<World>, SaveError> {
+    let edge = match read_u8(reader)? {
+        0 => return Ok(None),
+        index => Edge::from_index(index - 1)
+            .ok_or_else(|| SaveError::Corrupted(format!("unknown world edge {}", index)))?,
+    };
+    let x = i32::from_le_bytes(read_array(reader)?);
+    let y = i32::from_le_bytes(read_array(reader)?);
+    let width = u32::from_le_bytes(read_array(reader)?);
+    let height = u32::from_le_bytes(read_array(reader)?);
+    World::new((x, y), (width, height), edge)
+        .map(Some)
+        .ok_or_else(|| {
+            SaveError::Corrupted(format!(
+                "world of {} x {} at ({}, {}) does not fit in the grid",
+                width, height, x, y
+            ))
+        })
+}
+
+// Reuse a known rule when the saved one matches it, so its index stays meaningful. Otherwise
+// the saved rule replaces the one with its name, loading a snapshot again adds no duplicate.
+fn select_or_add_rule(gamestate: &mut Gamestate, rule: Rule) {
+    let existing = gamestate.get_rules().iter().position(|known| {
+        known.get_notation() == rule.get_notation() && known.get_colors() == rule.get_colors()
+    });
+    let index = match existing {
+        Some(index) => index,
+        None => gamestate.merge_rule(rule),
+    };
+    gamestate.select_rule(index);
+}
+
+// UTF-8 text preceded by its length as a u32
+fn read_text(reader: &mut impl Read, what: &str) -> Result<String, SaveError> {
+    let length = u32::from_le_bytes(read_array(reader)?) as usize;
+    if length > MAX_TEXT_LENGTH {
+        return Err(SaveError::Corrupted(format!(
+            "{} of {} bytes",
+            what, length
+        )));
+    }
+    let mut text = vec![0; length];
+    reader.read_exact(&mut text)?;
+    String::from_utf8(text)
+        .map_err(|_| SaveError::Corrupted(format!("{} is not valid UTF-8", what)))
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> Result<[u8; N], SaveError> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u8(reader: &mut impl Read) -> Result<u8, SaveError> {
+    Ok(read_array::<1>(reader)?[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Gamestate {
+        let mut gamestate = Gamestate::new();
+        let rule = Rule::new("Square builder", "LRRRRRLLR", (0x000000, 0xFFFFFF)).unwrap();
+        let index = gamestate.add_rule(rule);
+        gamestate.select_rule(index);
+        gamestate.add_ant(Ant::place_ant(0, 0, Direction::Up));
+        gamestate.add_ant(Ant::place_ant(7, -3, Direction::Left));
+        gamestate.step(1000);
+        gamestate.set_speed(1 << 40);
+        gamestate
+    }
+
+    fn bytes(gamestate: &Gamestate) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_gamestate(gamestate, &mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn snapshots_round_trip() {
+        let saved = sample();
+        let mut loaded = Gamestate::new();
+        read_gamestate(&mut loaded, &mut bytes(&saved).as_slice()).unwrap();
+
+        assert_eq!(loaded.get_rule().get_name(), "Square builder");
+        assert_eq!(loaded.get_rule().get_notation(), "LRRRRRLLR");
+        assert_eq!(loaded.get_iteration(), saved.get_iteration());
+        assert_eq!(loaded.get_speed(), saved.get_speed());
+        let mut cells: Vec<_> = loaded.get_grid().iter().collect();
+        let mut expected: Vec<_> = saved.get_grid().iter().collect();
+        cells.sort();
+        expected.sort();
+        assert_eq!(cells, expected);
+        let ants = |gamestate: &Gamestate| {
+            gamestate
+                .get_ants()
+                .map(|ant| (ant.x, ant.y, ant.heading, ant.state))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ants(&loaded), ants(&saved));
+    }
+
+    #[test]
+    fn loading_again_adds_no_duplicate_rule() {
+        let snapshot = bytes(&sample());
+        let mut loaded = Gamestate::new();
+        read_gamestate(&mut loaded, &mut snapshot.as_slice()).unwrap();
+        let rule_count = loaded.get_rules().len();
+        read_gamestate(&mut loaded, &mut snapshot.as_slice()).unwrap();
+        assert_eq!(loaded.get_rules().len(), rule_count);
+    }
+
+    #[test]
+    fn damaged_snapshots_are_rejected_and_leave_the_gamestate_alone() {
+        let snapshot = bytes(&sample());
+        let mut gamestate = Gamestate::new();
+        gamestate.add_ant(Ant::place_ant(1, 1, Direction::Down));
+
+        let mut not_a_save = snapshot.clone();
+        not_a_save[0] = b'X';
+        assert!(matches!(
+            read_gamestate(&mut gamestate, &mut not_a_save.as_slice()),
+            Err(SaveError::NotASave)
+        ));
+
+        let mut future = snapshot.clone();
+        future[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            read_gamestate(&mut gamestate, &mut future.as_slice()),
+            Err(SaveError::UnsupportedVersion(_))
+        ));
+
+        for length in [10, snapshot.len() / 2, snapshot.len() - 1] {
+            assert!(matches!(
+                read_gamestate(&mut gamestate, &mut &snapshot[..length]),
+                Err(SaveError::Corrupted(_))
+            ));
+        }
+
+        // The state of the last ant is past the states of the rule
+        let mut bad_state = snapshot.clone();
+        *bad_state.last_mut().unwrap() = 9;
+        assert!(matches!(
+            read_gamestate(&mut gamestate, &mut bad_state.as_slice()),
+            Err(SaveError::Corrupted(_))
+        ));
+
+        assert_eq!(*gamestate.get_iteration(), 0);
+        assert_eq!(gamestate.get_total_ants(), 1);
+    }
+}
@@ -0,0 +1,211 @@
+//! Finite worlds. The grid itself is unbounded, a world keeps the ants and the painted cells
+//! inside a rectangle and decides what happens to an ant stepping over its edge.
+//!
+//! On the hexagonal lattice the rectangle is taken in axial coordinates, a parallelogram on screen.
+
+// Longest side of a world, which leaves room in the coordinates for the cells past its edges
+pub const MAX_WORLD_SIDE: u32 = 1 << 30;
+
+/// What an ant stepping over the edge of a world does
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Edge {
+    // Come back from the opposite edge, the world is a torus
+    Wrap,
+    // Turn around and stay on its cell
+    Wall,
+    // Leave the world and disappear
+    Kill,
+}
+
+impl Edge {
+    pub fn all() -> [Edge; 3] {
+        [Edge::Wrap, Edge::Wall, Edge::Kill]
+    }
+
+    pub fn as_index(&self) -> u8 {
+        match self {
+            Edge::Wrap => 0,
+            Edge::Wall => 1,
+            Edge::Kill => 2,
+        }
+    }
+
+    pub fn from_index(index: u8) -> Option<Edge> {
+        Edge::all().get(index as usize).copied()
+    }
+
+    // Name used on the command line
+    pub fn get_name(&self) -> &str {
+        match self {
+            Edge::Wrap => "wrap",
+            Edge::Wall => "wall",
+            Edge::Kill => "kill",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Edge> {
+        Edge::all()
+            .into_iter()
+            .find(|edge| edge.get_name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Where an ant moving toward a cell ends up
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Landing {
+    // On the cell, or on the cell it stands for across a wrapping edge
+    Cell((i32, i32)),
+    // Against a wall
+    Wall,
+    // Out of the world
+    Outside,
+}
+
+/// Rectangle of cells the ants live in, with the behaviour of its edges
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct World {
+    // Top left cell
+    origin: (i32, i32),
+    width: u32,
+    height: u32,
+    edge: Edge,
+}
+
+impl World {
+    // None when a side is 0 or longer than MAX_WORLD_SIDE, or when the world and the cells around
+    // it do not fit in the coordinates
+    pub fn new(origin: (i32, i32), size: (u32, u32), edge: Edge) -> Option<World> {
+        let fits = |start: i32, side: u32| {
+            (1..=MAX_WORLD_SIDE).contains(&side)
+                && start > i32::MIN
+                && start as i64 + side as i64 <= i32::MAX as i64
+        };
+        (fits(origin.0, size.0) && fits(origin.1, size.1)).then_some(World {
+            origin,
+            width: size.0,
+            height: size.1,
+            edge,
+        })
+    }
+
+    // World whose middle cell is the origin of the grid
+    pub fn centered(size: (u32, u32), edge: Edge) -> Option<World> {
+        let origin = (-((size.0 / 2) as i32), -((size.1 / 2) as i32));
+        World::new(origin, size, edge)
+    }
+
+    pub fn get_origin(&self) -> (i32, i32) {
+        self.origin
+    }
+
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn get_edge(&self) -> Edge {
+        self.edge
+    }
+
+    pub fn with_edge(&self, edge: Edge) -> World {
+        World { edge, ..*self }
+    }
+
+    // Inclusive rectangle (min_x, min_y, max_x, max_y) of the cells of the world
+    pub fn get_bounds(&self) -> (i32, i32, i32, i32) {
+        (
+            self.origin.0,
+            self.origin.1,
+            self.origin.0 + (self.width - 1) as i32,
+            self.origin.1 + (self.height - 1) as i32,
+        )
+    }
+
+    pub fn contains(&self, position: (i32, i32)) -> bool {
+        let (min_x, min_y, max_x, max_y) = self.get_bounds();
+        (min_x..=max_x).contains(&position.0) && (min_y..=max_y).contains(&position.1)
+    }
+
+    // Where an ant moving to a cell, inside the world or next to it, ends up
+    pub fn land(&self, position: (i32, i32)) -> Landing {
+        if self.contains(position) {
+            return Landing::Cell(position);
+        }
+        match self.edge {
+            Edge::Wrap => {
+                let wrap = |value: i32, start: i32, side: u32| {
+                    (start as i64 + (value as i64 - start as i64).rem_euclid(side as i64)) as i32
+                };
+                Landing::Cell((
+                    wrap(position.0, self.origin.0, self.width),
+                    wrap(position.1, self.origin.1, self.height),
+                ))
+            }
+            Edge::Wall => Landing::Wall,
+            Edge::Kill => Landing::Outside,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamemodes::langton::{Ant, Direction, Gamestate, StepLimit};
+
+    #[test]
+    fn worlds_must_fit_in_the_grid() {
+        assert!(World::new((0, 0), (0, 4), Edge::Wrap).is_none());
+        assert!(World::new((0, 0), (MAX_WORLD_SIDE + 1, 4), Edge::Wrap).is_none());
+        assert!(World::new((i32::MIN, 0), (4, 4), Edge::Wrap).is_none());
+        assert!(World::new((i32::MAX - 3, 0), (4, 4), Edge::Wrap).is_none());
+        let world = World::centered((5, 4), Edge::Wall).unwrap();
+        assert_eq!(world.get_bounds(), (-2, -2, 2, 1));
+    }
+
+    #[test]
+    fn each_edge_lands_differently() {
+        let world = World::new((-2, -2), (4, 3), Edge::Wrap).unwrap();
+        assert_eq!(world.land((1, 0)), Landing::Cell((1, 0)));
+        assert_eq!(world.land((2, 0)), Landing::Cell((-2, 0)));
+        assert_eq!(world.land((-3, -3)), Landing::Cell((1, 0)));
+        assert_eq!(world.with_edge(Edge::Wall).land((2, 0)), Landing::Wall);
+        assert_eq!(world.with_edge(Edge::Kill).land((2, 0)), Landing::Outside);
+    }
+
+    // Lone ant in a one cell world, every move crosses the edge
+    fn boxed_ant(edge: Edge) -> Gamestate {
+        let mut gamestate = Gamestate::new();
+        gamestate.set_world(World::new((0, 0), (1, 1), edge));
+        gamestate.add_ant(Ant::place_ant(0, 0, Direction::Up));
+        gamestate
+    }
+
+    #[test]
+    fn ants_wrap_bounce_or_die_at_the_edge() {
+        // Right, left and right again on the same cell
+        let mut wrapped = boxed_ant(Edge::Wrap);
+        wrapped.step(3);
+        let ant = wrapped.get_ants().next().unwrap();
+        assert_eq!((ant.x, ant.y, ant.heading), (0, 0, 1));
+
+        // Right on the blank cell, then turned around by the wall
+        let mut walled = boxed_ant(Edge::Wall);
+        walled.step(1);
+        let ant = walled.get_ants().next().unwrap();
+        assert_eq!((ant.x, ant.y, ant.heading), (0, 0, 3));
+
+        let mut killed = boxed_ant(Edge::Kill);
+        assert_eq!(killed.step(5), 5);
+        assert_eq!(killed.get_total_ants(), 0);
+        assert_eq!(killed.get_grid_value((0, 0)), 1);
+    }
+
+    #[test]
+    fn the_unbounded_grid_stops_at_the_end_of_the_coordinates() {
+        let mut gamestate = Gamestate::new();
+        gamestate.add_ant(Ant::place_ant(i32::MAX, 0, Direction::Up));
+        assert_eq!(gamestate.step(3), 0);
+        assert_eq!(gamestate.take_step_limit(), Some(StepLimit::Edge));
+        let ant = gamestate.get_ants().next().unwrap();
+        assert_eq!((ant.x, ant.y), (i32::MAX, 0));
+    }
+}
@@ -0,0 +1,338 @@
+use crate::config::structures::*;
+use langton_engine::gamemodes::langton::{Gamestate, Rule};
+use macroquad::prelude::KeyCode;
+use std::path::Path;
+
+// Rule file merged with the built-in rules at startup when it exists
+pub const RULE_FILE: &str = "rules.toml";
+
+// Colors of rules that give neither a gradient nor explicit colors
+const DEFAULT_GRADIENT: (u32, u32) = (0x000000, 0xFFFFFF);
+
+pub fn parse_color(value: &ColorValue) -> Result<u32, String> {
+    match value {
+        ColorValue::Number(color) if *color <= 0xFFFFFF => Ok(*color),
+        ColorValue::Number(color) => Err(format!("color {:#x} is not 0xRRGGBB", color)),
+        ColorValue::Text(text) => {
+            let digits = text.strip_prefix('#').unwrap_or(text);
+            match u32::from_str_radix(digits, 16) {
+                Ok(color) if digits.len() == 6 => Ok(color),
+                _ => Err(format!("color \"{}\" is not #RRGGBB", text)),
+            }
+        }
+    }
+}
+
+pub fn build_rule(entry: &RuleEntry) -> Result<Rule, String> {
+    let mut rule = match (&entry.gradient, &entry.colors) {
+        (Some(_), Some(_)) => return Err("give either gradient or colors, not both".to_string()),
+        (Some([start, end]), None) => Rule::new(
+            &entry.name,
+            &entry.turns,
+            (parse_color(start)?, parse_color(end)?),
+        ),
+        (None, Some(colors)) => {
+            let colors = colors
+                .iter()
+                .map(parse_color)
+                .collect::<Result<Vec<u32>, String>>()?;
+            Rule::with_colors(&entry.name, &entry.turns, colors)
+        }
+        (None, None) => Rule::new(&entry.name, &entry.turns, DEFAULT_GRADIENT),
+    }
+    .map_err(|error| error.to_string())?;
+    rule.set_description(&entry.description);
+    Ok(rule)
+}
+
+// Merge the rules of a rule file into the gamestate, rules sharing a name with an existing one
+// replace it. Returns one message per rejected entry, or an error if the file is unusable.
+pub fn merge_rule_file(gamestate: &mut Gamestate, path: &Path) -> Result<Vec<String>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|error| format!("Could not read {}: {}", path.display(), error))?;
+    let file: RuleFile = toml::from_str(&text)
+        .map_err(|error| format!("Could not parse {}: {}", path.display(), error))?;
+
+    let mut rejected = Vec::new();
+    for entry in &file.rule {
+        match build_rule(entry) {
+            Ok(rule) => {
+                gamestate.merge_rule(rule);
+            }
+            Err(error) => rejected.push(format!(
+                "{}: rule \"{}\" skipped, {}",
+                path.display(),
+                entry.name,
+                error
+            )),
+        }
+    }
+    Ok(rejected)
+}
+
+// Merge the default rule file if present, returning the problems to report
+pub fn load_default_rule_file(gamestate: &mut Gamestate) -> Vec<String> {
+    let path = Path::new(RULE_FILE);
+    if !path.exists() {
+        return Vec::new();
+    }
+    match merge_rule_file(gamestate, path) {
+        Ok(rejected) => rejected,
+        Err(error) => vec![error],
+    }
+}
+
+// Keybindings read at startup and written by the rebinding screen
+pub const KEYBINDING_FILE: &str = "keybindings.toml";
+
+// Held with the mouse wheel to turn the placement heading, and with Z and Y to undo and redo.
+// Actions bound to keys never trigger while it is held.
+pub const MODIFIER_KEY: KeyCode = KeyCode::LeftControl;
+
+// Keys with a fixed meaning in the window, they cannot be bound to an action
+pub const RESERVED_KEYS: [KeyCode; 5] = [
+    KeyCode::Escape,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::LeftShift,
+    MODIFIER_KEY,
+];
+
+// Keys accepted in the keybinding file, named as in the macroquad KeyCode enum
+const BINDABLE_KEYS: [KeyCode; 87] = [
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Space,
+    KeyCode::Backspace,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Minus,
+    KeyCode::Equal,
+    KeyCode::LeftBracket,
+    KeyCode::RightBracket,
+    KeyCode::Backslash,
+    KeyCode::Semicolon,
+    KeyCode::Apostrophe,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::GraveAccent,
+    KeyCode::LeftControl,
+    KeyCode::RightControl,
+    KeyCode::LeftAlt,
+    KeyCode::RightAlt,
+    KeyCode::RightShift,
+    KeyCode::Kp0,
+    KeyCode::Kp1,
+    KeyCode::Kp2,
+    KeyCode::Kp3,
+    KeyCode::Kp4,
+    KeyCode::Kp5,
+    KeyCode::Kp6,
+    KeyCode::Kp7,
+    KeyCode::Kp8,
+    KeyCode::Kp9,
+    KeyCode::KpAdd,
+];
+
+pub fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key)
+}
+
+pub fn key_from_name(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS
+        .into_iter()
+        .find(|key| key_name(*key).eq_ignore_ascii_case(name))
+}
+
+// Why a key cannot be bound to an action, if it cannot
+pub fn check_bindable(key: KeyCode) -> Result<(), String> {
+    if RESERVED_KEYS.contains(&key) {
+        Err(format!("{} is reserved", key_name(key)))
+    } else if !BINDABLE_KEYS.contains(&key) {
+        Err(format!("{} cannot be bound", key_name(key)))
+    } else {
+        Ok(())
+    }
+}
+
+// Build keybindings from the text of a keybinding file. Returns one message per rejected entry,
+// or an error if the file is unusable.
+pub fn parse_keybindings(text: &str) -> Result<(Keybindings, Vec<String>), String> {
+    let file: KeybindingFile = toml::from_str(text).map_err(|error| error.to_string())?;
+    let preset = match &file.preset {
+        Some(name) => KeyboardPreset::from_name(name)
+            .ok_or_else(|| format!("unknown preset \"{}\" (expected azerty or qwerty)", name))?,
+        None => KeyboardPreset::Azerty,
+    };
+
+    let mut keybindings = Keybindings::new(preset);
+    let mut rejected = Vec::new();
+    for (name, key) in &file.keys {
+        let Some(action) = Action::from_name(name) else {
+            rejected.push(format!("unknown action \"{}\" skipped", name));
+            continue;
+        };
+        // An empty key leaves the action unbound
+        if key.is_empty() {
+            keybindings.bind(action, None);
+            continue;
+        }
+        match key_from_name(key).ok_or_else(|| format!("unknown key \"{}\"", key)) {
+            Ok(key) => match check_bindable(key) {
+                Ok(()) => keybindings.bind(action, Some(key)),
+                Err(error) => rejected.push(format!("\"{}\" skipped, {}", name, error)),
+            },
+            Err(error) => rejected.push(format!("\"{}\" skipped, {}", name, error)),
+        }
+    }
+    Ok((keybindings, rejected))
+}
+
+// Read the default keybinding file if present, falling back to the AZERTY preset.
+// Returns the keybindings and the problems to report.
+pub fn load_default_keybindings() -> (Keybindings, Vec<String>) {
+    let path = Path::new(KEYBINDING_FILE);
+    if !path.exists() {
+        return (Keybindings::new(KeyboardPreset::Azerty), Vec::new());
+    }
+    let result = std::fs::read_to_string(path)
+        .map_err(|error| error.to_string())
+        .and_then(|text| parse_keybindings(&text));
+    match result {
+        Ok((keybindings, rejected)) => (
+            keybindings,
+            rejected
+                .into_iter()
+                .map(|message| format!("{}: {}", path.display(), message))
+                .collect(),
+        ),
+        Err(error) => (
+            Keybindings::new(KeyboardPreset::Azerty),
+            vec![format!("Could not read {}: {}", path.display(), error)],
+        ),
+    }
+}
+
+// Write the preset of the keybindings and the keys that differ from it
+pub fn save_keybindings(keybindings: &Keybindings, path: &Path) -> Result<(), String> {
+    let preset = keybindings.get_preset();
+    let file = KeybindingFile {
+        preset: Some(preset.get_name().to_string()),
+        keys: keybindings
+            .get_bindings()
+            .iter()
+            .filter(|(action, key)| *key != Some(preset.get_key(*action)))
+            .map(|(action, key)| (action.get_name(), key.map(key_name).unwrap_or_default()))
+            .collect(),
+    };
+    let text = toml::to_string(&file).map_err(|error| error.to_string())?;
+    std::fs::write(path, text)
+        .map_err(|error| format!("Could not write {}: {}", path.display(), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_give_every_action_a_distinct_key() {
+        for preset in [KeyboardPreset::Azerty, KeyboardPreset::Qwerty] {
+            let keybindings = Keybindings::new(preset);
+            let mut keys: Vec<KeyCode> = Action::all()
+                .into_iter()
+                .map(|action| keybindings.get_key(action).unwrap())
+                .collect();
+            assert!(keys.iter().all(|key| check_bindable(*key).is_ok()));
+            let count = keys.len();
+            keys.sort_by_key(|key| *key as u32);
+            keys.dedup();
+            assert_eq!(keys.len(), count);
+        }
+    }
+
+    #[test]
+    fn keybinding_files_override_the_preset() {
+        let text = "preset = \"qwerty\"\n[keys]\nsave = \"F\"\nrecord = \"\"\n";
+        let (keybindings, rejected) = parse_keybindings(text).unwrap();
+        assert!(rejected.is_empty());
+        assert_eq!(keybindings.get_preset(), KeyboardPreset::Qwerty);
+        assert_eq!(keybindings.get_key(Action::PanUp), Some(KeyCode::W));
+        // The step action had F and takes the previous key of save
+        assert_eq!(keybindings.get_key(Action::Save), Some(KeyCode::F));
+        assert_eq!(keybindings.get_key(Action::Step), Some(KeyCode::P));
+        assert_eq!(keybindings.get_key(Action::Record), None);
+        assert_eq!(keybindings.get_key(Action::GamemodeMenu), Some(KeyCode::M));
+    }
+
+    #[test]
+    fn bad_keybinding_entries_are_reported() {
+        let text = "[keys]\njump = \"J\"\npause = \"Banana\"\nstep = \"Escape\"\nload = \"K\"\n";
+        let (keybindings, rejected) = parse_keybindings(text).unwrap();
+        assert_eq!(keybindings.get_preset(), KeyboardPreset::Azerty);
+        assert_eq!(rejected.len(), 3);
+        assert!(rejected.iter().any(|message| message.contains("jump")));
+        assert!(rejected.iter().any(|message| message.contains("Banana")));
+        assert!(rejected.iter().any(|message| message.contains("Escape")));
+        assert_eq!(keybindings.get_key(Action::Pause), Some(KeyCode::Space));
+        assert_eq!(keybindings.get_key(Action::Step), Some(KeyCode::F));
+        assert_eq!(keybindings.get_key(Action::Load), Some(KeyCode::K));
+
+        assert!(parse_keybindings("preset = \"dvorak\"").is_err());
+        assert!(parse_keybindings("keys = 3").is_err());
+    }
+}
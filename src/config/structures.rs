@@ -0,0 +1,320 @@
+use crate::config::functions::MODIFIER_KEY;
+use macroquad::prelude::{KeyCode, is_key_down, is_key_pressed};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A color written either as a number (0xRRGGBB) or as a "#RRGGBB" string
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum ColorValue {
+    Number(u32),
+    Text(String),
+}
+
+/// One `[[rule]]` table of a rule file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleEntry {
+    pub name: String,
+    pub turns: String,
+    // Either gradient endpoints or one color per state
+    pub gradient: Option<[ColorValue; 2]>,
+    pub colors: Option<Vec<ColorValue>>,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleFile {
+    #[serde(default)]
+    pub rule: Vec<RuleEntry>,
+}
+
+/// Action of the interactive window that can be bound to a key
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    ZoomIn,
+    ZoomOut,
+    Pause,
+    Step,
+    StepBack,
+    // Run the simulation backward or forward
+    Reverse,
+    ClearAnts,
+    ClearGrid,
+    SpeedUp,
+    ResetSpeed,
+    NextRule,
+    PreviousRule,
+    // One of the first twelve rules, starting from 1
+    SelectRule(u8),
+    CursorWider,
+    CursorNarrower,
+    CursorTaller,
+    CursorShorter,
+    NextTool,
+    NextPaintState,
+    RotateHeading,
+    CopySelection,
+    RotatePattern,
+    MirrorPattern,
+    // Unbounded grid, then a world with wrapping, wall and killing edges
+    CycleWorld,
+    // Let undo go back through simulation steps, not only through edits
+    SimulationHistory,
+    // Snapshot of the simulation written and read back
+    Save,
+    Load,
+    // Image of the whole pattern
+    Export,
+    // Start or stop recording an animation, with shift for the whole pattern
+    Record,
+    GamemodeMenu,
+    KeybindingMenu,
+}
+
+impl Action {
+    // Every action, in the order of the rebinding screen
+    pub fn all() -> Vec<Action> {
+        let mut actions = vec![
+            Action::PanUp,
+            Action::PanDown,
+            Action::PanLeft,
+            Action::PanRight,
+            Action::ZoomIn,
+            Action::ZoomOut,
+            Action::Pause,
+            Action::Step,
+            Action::StepBack,
+            Action::Reverse,
+            Action::ClearAnts,
+            Action::ClearGrid,
+            Action::SpeedUp,
+            Action::ResetSpeed,
+            Action::NextRule,
+            Action::PreviousRule,
+        ];
+        actions.extend((1..=12).map(Action::SelectRule));
+        actions.extend([
+            Action::CursorWider,
+            Action::CursorNarrower,
+            Action::CursorTaller,
+            Action::CursorShorter,
+            Action::NextTool,
+            Action::NextPaintState,
+            Action::RotateHeading,
+            Action::CopySelection,
+            Action::RotatePattern,
+            Action::MirrorPattern,
+            Action::CycleWorld,
+            Action::SimulationHistory,
+            Action::Save,
+            Action::Load,
+            Action::Export,
+            Action::Record,
+            Action::GamemodeMenu,
+            Action::KeybindingMenu,
+        ]);
+        actions
+    }
+
+    // Name used in the keybinding file
+    pub fn get_name(&self) -> String {
+        match self {
+            Action::PanUp => "pan_up".to_string(),
+            Action::PanDown => "pan_down".to_string(),
+            Action::PanLeft => "pan_left".to_string(),
+            Action::PanRight => "pan_right".to_string(),
+            Action::ZoomIn => "zoom_in".to_string(),
+            Action::ZoomOut => "zoom_out".to_string(),
+            Action::Pause => "pause".to_string(),
+            Action::Step => "step".to_string(),
+            Action::StepBack => "step_back".to_string(),
+            Action::Reverse => "reverse".to_string(),
+            Action::ClearAnts => "clear_ants".to_string(),
+            Action::ClearGrid => "clear_grid".to_string(),
+            Action::SpeedUp => "speed_up".to_string(),
+            Action::ResetSpeed => "reset_speed".to_string(),
+            Action::NextRule => "next_rule".to_string(),
+            Action::PreviousRule => "previous_rule".to_string(),
+            Action::SelectRule(rule) => format!("select_rule_{}", rule),
+            Action::CursorWider => "cursor_wider".to_string(),
+            Action::CursorNarrower => "cursor_narrower".to_string(),
+            Action::CursorTaller => "cursor_taller".to_string(),
+            Action::CursorShorter => "cursor_shorter".to_string(),
+            Action::NextTool => "next_tool".to_string(),
+            Action::NextPaintState => "next_paint_state".to_string(),
+            Action::RotateHeading => "rotate_heading".to_string(),
+            Action::CopySelection => "copy_selection".to_string(),
+            Action::RotatePattern => "rotate_pattern".to_string(),
+            Action::MirrorPattern => "mirror_pattern".to_string(),
+            Action::CycleWorld => "cycle_world".to_string(),
+            Action::SimulationHistory => "simulation_history".to_string(),
+            Action::Save => "save".to_string(),
+            Action::Load => "load".to_string(),
+            Action::Export => "export".to_string(),
+            Action::Record => "record".to_string(),
+            Action::GamemodeMenu => "gamemode_menu".to_string(),
+            Action::KeybindingMenu => "keybinding_menu".to_string(),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::all()
+            .into_iter()
+            .find(|action| action.get_name() == name)
+    }
+}
+
+/// Starting point of the keybindings, the pan keys follow the letter layout of the keyboard
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum KeyboardPreset {
+    Azerty,
+    Qwerty,
+}
+
+impl KeyboardPreset {
+    pub fn all() -> [KeyboardPreset; 2] {
+        [KeyboardPreset::Azerty, KeyboardPreset::Qwerty]
+    }
+
+    pub fn get_name(&self) -> &str {
+        match self {
+            KeyboardPreset::Azerty => "azerty",
+            KeyboardPreset::Qwerty => "qwerty",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<KeyboardPreset> {
+        KeyboardPreset::all()
+            .into_iter()
+            .find(|preset| preset.get_name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn get_key(&self, action: Action) -> KeyCode {
+        // Letters sitting at the same place on both layouts
+        let (up, left, zoom_in, zoom_out) = match self {
+            KeyboardPreset::Azerty => (KeyCode::Z, KeyCode::Q, KeyCode::A, KeyCode::W),
+            KeyboardPreset::Qwerty => (KeyCode::W, KeyCode::A, KeyCode::Q, KeyCode::Z),
+        };
+        match action {
+            Action::PanUp => up,
+            Action::PanDown => KeyCode::S,
+            Action::PanLeft => left,
+            Action::PanRight => KeyCode::D,
+            Action::ZoomIn => zoom_in,
+            Action::ZoomOut => zoom_out,
+            Action::Pause => KeyCode::Space,
+            Action::Step => KeyCode::F,
+            Action::StepBack => KeyCode::U,
+            Action::Reverse => KeyCode::N,
+            Action::ClearAnts => KeyCode::R,
+            Action::ClearGrid => KeyCode::T,
+            Action::SpeedUp => KeyCode::J,
+            Action::ResetSpeed => KeyCode::K,
+            Action::NextRule => KeyCode::PageDown,
+            Action::PreviousRule => KeyCode::PageUp,
+            Action::SelectRule(rule) => FUNCTION_KEYS[(rule as usize - 1) % FUNCTION_KEYS.len()],
+            Action::CursorWider => KeyCode::Right,
+            Action::CursorNarrower => KeyCode::Left,
+            Action::CursorTaller => KeyCode::Up,
+            Action::CursorShorter => KeyCode::Down,
+            Action::NextTool => KeyCode::C,
+            Action::NextPaintState => KeyCode::X,
+            Action::RotateHeading => KeyCode::H,
+            Action::CopySelection => KeyCode::G,
+            Action::RotatePattern => KeyCode::O,
+            Action::MirrorPattern => KeyCode::I,
+            Action::CycleWorld => KeyCode::Y,
+            Action::SimulationHistory => KeyCode::Home,
+            Action::Save => KeyCode::P,
+            Action::Load => KeyCode::L,
+            Action::Export => KeyCode::E,
+            Action::Record => KeyCode::V,
+            Action::GamemodeMenu => KeyCode::M,
+            Action::KeybindingMenu => KeyCode::B,
+        }
+    }
+}
+
+const FUNCTION_KEYS: [KeyCode; 12] = [
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+];
+
+/// Key of every action, an action without a key cannot be triggered
+pub struct Keybindings {
+    preset: KeyboardPreset,
+    keys: Vec<(Action, Option<KeyCode>)>,
+}
+
+impl Keybindings {
+    pub fn new(preset: KeyboardPreset) -> Self {
+        Keybindings {
+            preset,
+            keys: Action::all()
+                .into_iter()
+                .map(|action| (action, Some(preset.get_key(action))))
+                .collect(),
+        }
+    }
+
+    pub fn get_preset(&self) -> KeyboardPreset {
+        self.preset
+    }
+
+    pub fn get_bindings(&self) -> &[(Action, Option<KeyCode>)] {
+        &self.keys
+    }
+
+    pub fn get_key(&self, action: Action) -> Option<KeyCode> {
+        self.keys
+            .iter()
+            .find(|(bound, _)| *bound == action)
+            .and_then(|(_, key)| *key)
+    }
+
+    // Bind a key to an action, an action already using that key takes the previous key instead
+    pub fn bind(&mut self, action: Action, key: Option<KeyCode>) {
+        let previous = self.get_key(action);
+        for (bound, bound_key) in self.keys.iter_mut() {
+            if *bound == action {
+                *bound_key = key;
+            } else if key.is_some() && *bound_key == key {
+                *bound_key = previous;
+            }
+        }
+    }
+
+    pub fn is_pressed(&self, action: Action) -> bool {
+        !is_key_down(MODIFIER_KEY) && self.get_key(action).is_some_and(is_key_pressed)
+    }
+
+    pub fn is_down(&self, action: Action) -> bool {
+        !is_key_down(MODIFIER_KEY) && self.get_key(action).is_some_and(is_key_down)
+    }
+}
+
+/// Keybinding file: a preset and the keys that differ from it, by action name
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeybindingFile {
+    pub preset: Option<String>,
+    #[serde(default)]
+    pub keys: BTreeMap<String, String>,
+}
@@ -0,0 +1,275 @@
+use crate::export::structures::{ExportOptions, View};
+use crate::objects::functions::{
+    HEX_HEIGHT_RATIO, ant_color, hex_axial_to_world, hex_world_to_axial,
+};
+use langton_engine::gamemodes::Gamemode;
+use langton_engine::gamemodes::langton::Lattice;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+// Largest side of a PNG image
+const MAX_IMAGE_SIDE: u64 = i32::MAX as u64;
+// Rows are streamed, this only keeps a highway from turning into an endless export
+const MAX_IMAGE_PIXELS: u64 = 1 << 32;
+
+// Color of states missing from the palette
+const MISSING_COLOR: [u8; 3] = [255, 0, 255];
+
+// Triangle drawn over the cell of an ant, in pixels of the image
+struct AntMarker {
+    center: (f64, f64),
+    // Unit vector toward the cell the ant moves to next
+    forward: (f64, f64),
+    radius: f64,
+    color: [u8; 3],
+}
+
+impl AntMarker {
+    fn contains(&self, pixel: (f64, f64)) -> bool {
+        let (x, y) = (pixel.0 - self.center.0, pixel.1 - self.center.1);
+        let ahead = (x * self.forward.0 + y * self.forward.1) / self.radius;
+        let aside = (y * self.forward.0 - x * self.forward.1) / self.radius;
+        (-1.0..=1.0).contains(&ahead) && aside.abs() <= (1.0 - ahead) / 2.0
+    }
+}
+
+// World position of the center of a cell, a cell is one world unit wide
+fn cell_center(lattice: Lattice, position: (i32, i32)) -> (f64, f64) {
+    match lattice {
+        Lattice::Square => (position.0 as f64 + 0.5, position.1 as f64 + 0.5),
+        Lattice::Hexagonal => {
+            let (left, top) = hex_axial_to_world(position, 1.0);
+            (left as f64 + 0.5, (top + HEX_HEIGHT_RATIO / 2.0) as f64)
+        }
+    }
+}
+
+// Cell under a world position
+fn world_to_cell(lattice: Lattice, world: (f64, f64)) -> (i32, i32) {
+    match lattice {
+        Lattice::Square => (world.0.floor() as i32, world.1.floor() as i32),
+        Lattice::Hexagonal => hex_world_to_axial((world.0 as f32, world.1 as f32), 1.0),
+    }
+}
+
+// World area (left, top, right, bottom) covered by a set of cells
+pub fn world_extent(
+    lattice: Lattice,
+    cells: impl Iterator<Item = (i32, i32)>,
+) -> Option<(f64, f64, f64, f64)> {
+    cells
+        .map(|cell| match lattice {
+            Lattice::Square => (cell.0 as f64, cell.1 as f64, 1.0, 1.0),
+            Lattice::Hexagonal => {
+                let (left, top) = hex_axial_to_world(cell, 1.0);
+                (left as f64, top as f64, 1.0, HEX_HEIGHT_RATIO as f64)
+            }
+        })
+        .fold(None, |extent, (left, top, width, height)| {
+            let (right, bottom) = (left + width, top + height);
+            Some(match extent {
+                None => (left, top, right, bottom),
+                Some((min_left, min_top, max_right, max_bottom)) => (
+                    left.min(min_left),
+                    top.min(min_top),
+                    right.max(max_right),
+                    bottom.max(max_bottom),
+                ),
+            })
+        })
+}
+
+// Position and heading of the ants to draw over the cells
+pub fn collect_ants(gamemode: &dyn Gamemode) -> Vec<((i32, i32), u8)> {
+    match gamemode.as_langton() {
+        Some(langton) => langton
+            .get_ants()
+            .map(|ant| ((ant.x, ant.y), ant.heading))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+// World area covering every painted cell and the given ants
+pub fn pattern_extent(
+    gamemode: &dyn Gamemode,
+    ants: &[((i32, i32), u8)],
+) -> Option<(f64, f64, f64, f64)> {
+    let lattice = gamemode.get_lattice();
+    let grid = gamemode.get_grid();
+    let ant_cells = ants.iter().map(|(position, _)| *position);
+    match lattice {
+        // The corners of the bounding box are enough on the square lattice
+        Lattice::Square => world_extent(
+            lattice,
+            grid.bounding_box()
+                .into_iter()
+                .flat_map(|(min_x, min_y, max_x, max_y)| [(min_x, min_y), (max_x, max_y)])
+                .chain(ant_cells),
+        ),
+        // An axial rectangle is a parallelogram on screen, follow the cells instead
+        Lattice::Hexagonal => world_extent(
+            lattice,
+            grid.iter().map(|(position, _)| position).chain(ant_cells),
+        ),
+    }
+}
+
+// Render a view of the gamemode row by row, as 8 bit RGB pixels
+pub fn render_view(
+    gamemode: &dyn Gamemode,
+    view: &View,
+    ants: &[((i32, i32), u8)],
+    write_row: &mut dyn FnMut(&[u8]) -> Result<(), String>,
+) -> Result<(), String> {
+    let lattice = gamemode.get_lattice();
+    let grid = gamemode.get_grid();
+    let palette: Vec<[u8; 3]> = gamemode
+        .get_state_colors()
+        .iter()
+        .map(|color| [(color >> 16) as u8, (color >> 8) as u8, *color as u8])
+        .collect();
+    let color_of = |position: (i32, i32)| {
+        *palette
+            .get(grid.get(position) as usize)
+            .unwrap_or(&MISSING_COLOR)
+    };
+    let to_world = |pixel: u32, origin: f64| origin + (pixel as f64 + 0.5) * view.scale;
+
+    let markers: Vec<AntMarker> = ants
+        .iter()
+        .map(|(position, heading)| {
+            let center = cell_center(lattice, *position);
+            // An ant facing past the edge of the grid points away from the cell behind it
+            let next = match lattice.advance(*position, *heading) {
+                Some(next) => cell_center(lattice, next),
+                None => {
+                    let degree = lattice.get_degree();
+                    let behind = lattice
+                        .advance(*position, (*heading + degree / 2) % degree)
+                        .unwrap_or(*position);
+                    let behind = cell_center(lattice, behind);
+                    (2.0 * center.0 - behind.0, 2.0 * center.1 - behind.1)
+                }
+            };
+            let (forward_x, forward_y) = (next.0 - center.0, next.1 - center.1);
+            let length = (forward_x * forward_x + forward_y * forward_y).sqrt();
+            let color: [u8; 4] = ant_color(lattice, *heading).into();
+            AntMarker {
+                center: (
+                    (center.0 - view.left) / view.scale,
+                    (center.1 - view.top) / view.scale,
+                ),
+                forward: (forward_x / length, forward_y / length),
+                radius: 0.5 / view.scale,
+                color: [color[0], color[1], color[2]],
+            }
+        })
+        .collect();
+
+    // Square cells only depend on the column, and consecutive rows often show the same cells
+    let columns: Vec<i32> = (0..view.width)
+        .map(|pixel_x| to_world(pixel_x, view.left).floor() as i32)
+        .collect();
+    let mut cells = vec![0u8; view.width as usize * 3];
+    let mut row = vec![0u8; view.width as usize * 3];
+    let mut cell_row = None;
+    for pixel_y in 0..view.height {
+        let world_y = to_world(pixel_y, view.top);
+        match lattice {
+            Lattice::Square => {
+                let y = world_y.floor() as i32;
+                if cell_row != Some(y) {
+                    cell_row = Some(y);
+                    for (pixel, x) in cells.chunks_exact_mut(3).zip(&columns) {
+                        pixel.copy_from_slice(&color_of((*x, y)));
+                    }
+                }
+            }
+            Lattice::Hexagonal => {
+                for (pixel_x, pixel) in cells.chunks_exact_mut(3).enumerate() {
+                    let world = (to_world(pixel_x as u32, view.left), world_y);
+                    pixel.copy_from_slice(&color_of(world_to_cell(lattice, world)));
+                }
+            }
+        }
+        row.copy_from_slice(&cells);
+
+        let center_y = pixel_y as f64 + 0.5;
+        for marker in &markers {
+            if (center_y - marker.center.1).abs() > marker.radius {
+                continue;
+            }
+            let first = (marker.center.0 - marker.radius).floor().max(0.0) as usize;
+            let last = ((marker.center.0 + marker.radius).ceil().max(0.0) as usize)
+                .min(view.width as usize);
+            for pixel_x in first..last {
+                if marker.contains((pixel_x as f64 + 0.5, center_y)) {
+                    row[pixel_x * 3..pixel_x * 3 + 3].copy_from_slice(&marker.color);
+                }
+            }
+        }
+
+        write_row(&row)?;
+    }
+    Ok(())
+}
+
+// Write a PNG file whose RGB rows are produced one at a time by the render function
+pub fn write_png(
+    path: &str,
+    width: u32,
+    height: u32,
+    render: impl FnOnce(&mut dyn FnMut(&[u8]) -> Result<(), String>) -> Result<(), String>,
+) -> Result<(), String> {
+    let file =
+        File::create(path).map_err(|error| format!("Could not create {}: {}", path, error))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let write_error = |error: png::EncodingError| format!("Could not write {}: {}", path, error);
+    let mut writer = encoder.write_header().map_err(write_error)?;
+    let mut stream = writer.stream_writer().map_err(write_error)?;
+
+    render(&mut |row| {
+        stream
+            .write_all(row)
+            .map_err(|error| format!("Could not write {}: {}", path, error))
+    })?;
+
+    stream.finish().map_err(write_error)?;
+    writer.finish().map_err(write_error)
+}
+
+// Render every painted cell of the gamemode into a PNG file, one row at a time so that
+// patterns much larger than the screen fit in memory. Returns the size of the image.
+pub fn export_png(
+    gamemode: &dyn Gamemode,
+    path: &str,
+    options: &ExportOptions,
+) -> Result<(u32, u32), String> {
+    if options.cell_pixels == 0 {
+        return Err("Cells need at least one pixel".to_string());
+    }
+    let ants = if options.ants {
+        collect_ants(gamemode)
+    } else {
+        Vec::new()
+    };
+    let extent =
+        pattern_extent(gamemode, &ants).ok_or_else(|| "There is nothing to export".to_string())?;
+
+    let (width, height) = View::covering_size(extent, options.cell_pixels);
+    if width > MAX_IMAGE_SIDE || height > MAX_IMAGE_SIDE || width * height > MAX_IMAGE_PIXELS {
+        return Err(format!(
+            "The image would be {} x {} pixels, more than the {} allowed",
+            width, height, MAX_IMAGE_PIXELS
+        ));
+    }
+    let view = View::covering(extent, options.cell_pixels);
+
+    write_png(path, view.width, view.height, |write_row| {
+        render_view(gamemode, &view, &ants, write_row)
+    })?;
+    Ok((view.width, view.height))
+}
@@ -0,0 +1,231 @@
+use crate::export::functions::{
+    collect_ants, pattern_extent, render_view, world_extent, write_png,
+};
+use langton_engine::gamemodes::Gamemode;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// Delay between two frames of a recorded GIF, in hundredths of a second
+const GIF_FRAME_DELAY: u16 = 4;
+// Color quantization speed of GIF frames, from 1 (best) to 30 (fastest)
+const GIF_QUANTIZATION_SPEED: i32 = 10;
+
+/// How a pattern is rendered into an exported image
+pub struct ExportOptions {
+    // Width of a cell in pixels
+    pub cell_pixels: u32,
+    // Draw the ants over the cells
+    pub ants: bool,
+}
+
+impl ExportOptions {
+    pub fn new() -> Self {
+        ExportOptions {
+            cell_pixels: 1,
+            ants: false,
+        }
+    }
+}
+
+/// Part of the world drawn into an image, a cell is one world unit wide
+pub struct View {
+    // World position of the top left corner
+    pub left: f64,
+    pub top: f64,
+    // World units per pixel
+    pub scale: f64,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl View {
+    // Size in pixels of an image showing a world area with cells of the given width
+    pub fn covering_size(extent: (f64, f64, f64, f64), cell_pixels: u32) -> (u64, u64) {
+        let (left, top, right, bottom) = extent;
+        (
+            ((right - left) * cell_pixels as f64).ceil() as u64,
+            ((bottom - top) * cell_pixels as f64).ceil() as u64,
+        )
+    }
+
+    pub fn covering(extent: (f64, f64, f64, f64), cell_pixels: u32) -> Self {
+        let (width, height) = View::covering_size(extent, cell_pixels);
+        View {
+            left: extent.0,
+            top: extent.1,
+            scale: 1.0 / cell_pixels as f64,
+            width: width as u32,
+            height: height as u32,
+        }
+    }
+
+    // Center a world area in an image of a fixed size, cells keep their proportions
+    pub fn fitting(extent: (f64, f64, f64, f64), width: u32, height: u32) -> Self {
+        let (left, top, right, bottom) = extent;
+        let scale = ((right - left) / width as f64).max((bottom - top) / height as f64);
+        View {
+            left: (left + right - width as f64 * scale) / 2.0,
+            top: (top + bottom - height as f64 * scale) / 2.0,
+            scale,
+            width,
+            height,
+        }
+    }
+}
+
+/// Cells shown by the frames of a recording, scaled into the frame size
+pub enum RecordRegion {
+    // Inclusive rectangle of cells (min_x, min_y, max_x, max_y)
+    Fixed((i32, i32, i32, i32)),
+    // Bounding box of the pattern at the time of each frame
+    Fit,
+}
+
+/// Captures a gamemode every few iterations, into an animated GIF when the path ends
+/// with .gif and into numbered PNG files of a directory otherwise
+pub struct Recorder {
+    path: String,
+    region: RecordRegion,
+    frame_size: (u32, u32),
+    interval: u64,
+    ants: bool,
+    next_capture: u128,
+    frames: usize,
+    gif: Option<gif::Encoder<BufWriter<File>>>,
+}
+
+impl Recorder {
+    pub fn new(
+        path: &str,
+        region: RecordRegion,
+        frame_size: (u32, u32),
+        interval: u64,
+        ants: bool,
+    ) -> Self {
+        Recorder {
+            path: path.to_string(),
+            region,
+            frame_size: (frame_size.0.max(1), frame_size.1.max(1)),
+            interval: interval.max(1),
+            ants,
+            next_capture: 0,
+            frames: 0,
+            gif: None,
+        }
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn get_interval(&self) -> u64 {
+        self.interval
+    }
+
+    pub fn get_frames(&self) -> usize {
+        self.frames
+    }
+
+    fn is_gif(&self) -> bool {
+        self.path.to_ascii_lowercase().ends_with(".gif")
+    }
+
+    // Capture a frame once the iteration reached the next multiple of the interval
+    pub fn capture_if_due(&mut self, gamemode: &dyn Gamemode) -> Result<(), String> {
+        let iteration = gamemode.get_iteration();
+        if iteration < self.next_capture {
+            return Ok(());
+        }
+        self.capture(gamemode)?;
+        self.next_capture = (iteration / self.interval as u128 + 1) * self.interval as u128;
+        Ok(())
+    }
+
+    pub fn capture(&mut self, gamemode: &dyn Gamemode) -> Result<(), String> {
+        let ants = if self.ants {
+            collect_ants(gamemode)
+        } else {
+            Vec::new()
+        };
+        let extent = match self.region {
+            RecordRegion::Fixed((min_x, min_y, max_x, max_y)) => world_extent(
+                gamemode.get_lattice(),
+                [
+                    (min_x, min_y),
+                    (min_x, max_y),
+                    (max_x, min_y),
+                    (max_x, max_y),
+                ]
+                .into_iter(),
+            ),
+            RecordRegion::Fit => pattern_extent(gamemode, &ants),
+        };
+        // An empty grid shows the cell at the origin
+        let view = View::fitting(
+            extent.unwrap_or((0.0, 0.0, 1.0, 1.0)),
+            self.frame_size.0,
+            self.frame_size.1,
+        );
+
+        if !self.is_gif() {
+            fs::create_dir_all(&self.path)
+                .map_err(|error| format!("Could not create {}: {}", self.path, error))?;
+            let frame_path = Path::new(&self.path).join(format!("frame_{:06}.png", self.frames));
+            write_png(
+                &frame_path.to_string_lossy(),
+                view.width,
+                view.height,
+                |write_row| render_view(gamemode, &view, &ants, write_row),
+            )?;
+            self.frames += 1;
+            return Ok(());
+        }
+
+        let (Ok(width), Ok(height)) = (u16::try_from(view.width), u16::try_from(view.height))
+        else {
+            return Err(format!(
+                "GIF frames are limited to 65535 pixels, these are {} x {}",
+                view.width, view.height
+            ));
+        };
+        let mut pixels = Vec::with_capacity(view.width as usize * view.height as usize * 3);
+        render_view(gamemode, &view, &ants, &mut |row| {
+            pixels.extend_from_slice(row);
+            Ok(())
+        })?;
+
+        let path = &self.path;
+        let write_error =
+            |error: gif::EncodingError| format!("Could not write {}: {}", path, error);
+        let encoder = match &mut self.gif {
+            Some(encoder) => encoder,
+            None => {
+                let file = File::create(path)
+                    .map_err(|error| format!("Could not create {}: {}", path, error))?;
+                let mut encoder = gif::Encoder::new(BufWriter::new(file), width, height, &[])
+                    .map_err(write_error)?;
+                encoder
+                    .set_repeat(gif::Repeat::Infinite)
+                    .map_err(write_error)?;
+                self.gif.insert(encoder)
+            }
+        };
+        let mut frame = gif::Frame::from_rgb_speed(width, height, &pixels, GIF_QUANTIZATION_SPEED);
+        frame.delay = GIF_FRAME_DELAY;
+        encoder.write_frame(&frame).map_err(write_error)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    // Close the recording and return the number of frames written
+    pub fn finish(self) -> Result<usize, String> {
+        if let Some(encoder) = self.gif {
+            let write_error =
+                |error: std::io::Error| format!("Could not write {}: {}", self.path, error);
+            let mut writer = encoder.into_inner().map_err(write_error)?;
+            writer.flush().map_err(write_error)?;
+        }
+        Ok(self.frames)
+    }
+}
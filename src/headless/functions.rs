@@ -0,0 +1,671 @@
+use crate::config::functions::{RULE_FILE, merge_rule_file};
+use crate::export::functions::export_png;
+use crate::export::structures::{RecordRegion, Recorder};
+use crate::headless::structures::*;
+use langton_engine::explore::{Behaviour, enumerate_rules, explore_rule};
+use langton_engine::gamemodes::langton::{Gamestate, Lattice, Rule, Turn};
+use langton_engine::save::{load_from_file, save_to_file};
+use langton_engine::world::{Edge, MAX_WORLD_SIDE, World};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use thousands::Separable;
+
+pub const USAGE: &str = "Usage: Langton_Ant [--headless] [options]
+
+Without arguments the interactive window is opened.
+
+Headless options:
+  --rule <rule>          Rule index, rule name, turn string such as RLLR,
+                         hexagonal turn string such as L2NNL1L2L1 or
+                         turmite table such as {{{1,2,0},{0,8,0}}} (default: 0)
+  --rules <file>         Rule file merged with the built-in rules (default: rules.toml if present)
+  --steps <n>            Number of iterations to run (default: 0)
+  --ant <x,y[,heading]>  Place an ant, may be repeated (default: 0,0 facing up). The heading
+                         is up, right, down or left (n, e, s or w) on square rules, n, ne,
+                         se, s, sw or nw on hexagonal rules, or a number of clockwise steps
+                         from up below 4 or 6
+  --threads <n>          Threads stepping populations of thousands of ants, 1 to stay
+                         sequential (default: 0, one per core)
+  --no-fast-forward      Step a lone ant on a highway one iteration at a time instead of
+                         skipping whole periods
+  --world <WxH>          Keep the ants in a world of WxH cells centered on the origin instead
+                         of the unbounded grid
+  --edge <edge>          What an ant crossing the edge of the world does: wrap around the
+                         torus, turn around against a wall or be killed (default: wrap)
+  --load <file>          Resume from a snapshot instead of starting a new run
+  --save <file>          Write a snapshot once the run is over
+  --export <file>        Write a PNG image of the whole pattern once the run is over
+  --cell-pixels <n>      Width of a cell in the exported image (default: 1)
+  --export-ants          Draw the ants in the exported image and the recorded frames
+  --record <file>        Record the run as an animated GIF when the file ends with .gif,
+                         as numbered PNG frames in a directory otherwise
+  --record-every <n>     Iterations between two recorded frames (default: 100)
+  --record-region <x0,y0,x1,y1>
+                         Cells shown by the frames (default: the whole pattern, refitted
+                         at every frame)
+  --record-size <WxH>    Size of the recorded frames in pixels (default: 512x512)
+  --explore <length>     Run and classify every rule of 1 to <length> turns for --steps
+                         iterations, as highway, bounded, symmetric or chaotic
+  --alphabet <turns>     Turn letters of the explored rules (default: RL)
+  --report <file>        CSV file of the exploration (default: standard output)
+  --help                 Show this help message and exit";
+
+// Parse the arguments following the program name
+pub fn parse_arguments(args: &[String]) -> Result<HeadlessOptions, String> {
+    let mut options = HeadlessOptions::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--headless" => {}
+            "--rule" => {
+                let value = next_value(&mut args, arg)?;
+                options.rule = Some(match value.parse::<usize>() {
+                    Ok(index) => RuleChoice::Index(index),
+                    Err(_) => RuleChoice::Notation(value.to_string()),
+                });
+            }
+            "--steps" => {
+                let value = next_value(&mut args, arg)?;
+                options.steps = value
+                    .parse()
+                    .map_err(|_| format!("Invalid step count: {}", value))?;
+            }
+            "--ant" => {
+                let value = next_value(&mut args, arg)?;
+                options.ants.push(parse_ant(value)?);
+            }
+            "--threads" => {
+                let value = next_value(&mut args, arg)?;
+                options.threads = value
+                    .parse()
+                    .map_err(|_| format!("Invalid thread count: {}", value))?;
+            }
+            "--no-fast-forward" => options.fast_forward = false,
+            "--world" => {
+                let value = next_value(&mut args, arg)?;
+                options.world = Some(
+                    parse_size(value)
+                        .ok()
+                        .filter(|(width, height)| {
+                            *width <= MAX_WORLD_SIDE && *height <= MAX_WORLD_SIDE
+                        })
+                        .ok_or_else(|| {
+                            format!(
+                                "Invalid world size: {} (expected WxH, sides up to {})",
+                                value, MAX_WORLD_SIDE
+                            )
+                        })?,
+                );
+            }
+            "--edge" => {
+                let value = next_value(&mut args, arg)?;
+                options.edge = Some(Edge::from_name(value).ok_or_else(|| {
+                    format!("Invalid edge: {} (expected wrap, wall or kill)", value)
+                })?);
+            }
+            "--rules" => options.rules = Some(next_value(&mut args, arg)?.to_string()),
+            "--load" => options.load = Some(next_value(&mut args, arg)?.to_string()),
+            "--save" => options.save = Some(next_value(&mut args, arg)?.to_string()),
+            "--explore" => {
+                let value = next_value(&mut args, arg)?;
+                options.explore = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|length| *length > 0)
+                        .ok_or_else(|| format!("Invalid rule length: {}", value))?,
+                );
+            }
+            "--export" => options.export = Some(next_value(&mut args, arg)?.to_string()),
+            "--cell-pixels" => {
+                let value = next_value(&mut args, arg)?;
+                options.export_options.cell_pixels = value
+                    .parse()
+                    .ok()
+                    .filter(|pixels| *pixels > 0)
+                    .ok_or_else(|| format!("Invalid cell size: {}", value))?;
+            }
+            "--export-ants" => options.export_options.ants = true,
+            "--record" => options.record = Some(next_value(&mut args, arg)?.to_string()),
+            "--record-every" => {
+                let value = next_value(&mut args, arg)?;
+                options.record_every = value
+                    .parse()
+                    .ok()
+                    .filter(|interval| *interval > 0)
+                    .ok_or_else(|| format!("Invalid frame interval: {}", value))?;
+            }
+            "--record-region" => {
+                options.record_region = Some(parse_region(next_value(&mut args, arg)?)?)
+            }
+            "--record-size" => options.record_size = parse_size(next_value(&mut args, arg)?)?,
+            "--alphabet" => options.alphabet = parse_alphabet(next_value(&mut args, arg)?)?,
+            "--report" => options.report = Some(next_value(&mut args, arg)?.to_string()),
+            _ => return Err(format!("Unknown option: {}", arg)),
+        }
+    }
+
+    if options.load.is_some() && options.rule.is_some() {
+        return Err("--rule cannot be combined with --load".to_string());
+    }
+    if options.load.is_some() && options.world.is_some() {
+        return Err("--world cannot be combined with --load".to_string());
+    }
+    if options.edge.is_some() && options.world.is_none() {
+        return Err("--edge needs a --world".to_string());
+    }
+    if options.explore.is_some() {
+        if options.rule.is_some()
+            || options.load.is_some()
+            || options.save.is_some()
+            || options.export.is_some()
+            || options.record.is_some()
+            || options.world.is_some()
+        {
+            return Err(
+                "--explore cannot be combined with --rule, --load, --save, --export, --record or --world"
+                    .to_string(),
+            );
+        }
+        if options.steps == 0 {
+            return Err("--explore needs a number of --steps".to_string());
+        }
+    }
+    if options.load.is_none() && options.ants.is_empty() {
+        options.ants.push(((0, 0), None));
+    }
+    Ok(options)
+}
+
+fn next_value<'a>(
+    args: &mut impl Iterator<Item = &'a String>,
+    option: &str,
+) -> Result<&'a str, String> {
+    args.next()
+        .map(|value| value.as_str())
+        .ok_or_else(|| format!("Missing value for {}", option))
+}
+
+fn parse_alphabet(value: &str) -> Result<Vec<Turn>, String> {
+    let mut alphabet = Vec::new();
+    for letter in value.chars() {
+        let turn = Turn::from_letter(letter).ok_or_else(|| {
+            format!(
+                "Invalid turn in alphabet: {} (expected R, L, N or U)",
+                letter
+            )
+        })?;
+        if !alphabet.contains(&turn) {
+            alphabet.push(turn);
+        }
+    }
+    if alphabet.is_empty() {
+        return Err("The alphabet needs at least one turn".to_string());
+    }
+    Ok(alphabet)
+}
+
+fn parse_position(value: &str) -> Result<(i32, i32), String> {
+    let invalid = || format!("Invalid position: {} (expected x,y)", value);
+    let (x, y) = value.split_once(',').ok_or_else(invalid)?;
+    Ok((
+        x.trim().parse().map_err(|_| invalid())?,
+        y.trim().parse().map_err(|_| invalid())?,
+    ))
+}
+
+fn parse_ant(value: &str) -> Result<((i32, i32), Option<String>), String> {
+    let Some((position, heading)) = value
+        .rsplit_once(',')
+        .filter(|(position, _)| position.contains(','))
+    else {
+        return Ok((parse_position(value)?, None));
+    };
+    Ok((parse_position(position)?, Some(heading.trim().to_string())))
+}
+
+// Heading index of a heading name or number on a lattice
+fn parse_heading(heading: &str, lattice: Lattice) -> Result<u8, String> {
+    let names: &[&[&str]] = match lattice {
+        Lattice::Square => &[
+            &["up", "n"],
+            &["right", "e"],
+            &["down", "s"],
+            &["left", "w"],
+        ],
+        Lattice::Hexagonal => &[&["n"], &["ne"], &["se"], &["s"], &["sw"], &["nw"]],
+    };
+    let lowercase = heading.to_ascii_lowercase();
+    if let Some(index) = names
+        .iter()
+        .position(|aliases| aliases.contains(&lowercase.as_str()))
+    {
+        return Ok(index as u8);
+    }
+    match heading.parse::<u8>() {
+        Ok(index) if index < lattice.get_degree() => Ok(index),
+        _ => Err(format!(
+            "Invalid heading {} on a {:?} rule (expected {} or 0 to {})",
+            heading,
+            lattice,
+            names
+                .iter()
+                .map(|aliases| aliases.join("/"))
+                .collect::<Vec<_>>()
+                .join(", "),
+            lattice.get_degree() - 1
+        )),
+    }
+}
+
+fn parse_region(value: &str) -> Result<(i32, i32, i32, i32), String> {
+    let invalid = || format!("Invalid region: {} (expected x0,y0,x1,y1)", value);
+    let corners: Vec<i32> = value
+        .split(',')
+        .map(|coordinate| coordinate.trim().parse().map_err(|_| invalid()))
+        .collect::<Result<_, _>>()?;
+    let [x0, y0, x1, y1] = corners[..] else {
+        return Err(invalid());
+    };
+    Ok((x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)))
+}
+
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("Invalid size: {} (expected WxH)", value);
+    let (width, height) = value.split_once(['x', 'X']).ok_or_else(invalid)?;
+    let parse = |side: &str| {
+        side.trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|side| *side > 0)
+            .ok_or_else(invalid)
+    };
+    Ok((parse(width)?, parse(height)?))
+}
+
+// Build the gamestate described by the options
+pub fn build_gamestate(options: &HeadlessOptions) -> Result<Gamestate, String> {
+    let mut gamestate = Gamestate::new();
+    gamestate.set_thread_count(options.threads);
+    gamestate.set_fast_forward(options.fast_forward);
+
+    let rule_file = match &options.rules {
+        Some(path) => Some(path.as_str()),
+        None if Path::new(RULE_FILE).exists() => Some(RULE_FILE),
+        None => None,
+    };
+    if let Some(path) = rule_file {
+        for message in merge_rule_file(&mut gamestate, Path::new(path))? {
+            eprintln!("{}", message);
+        }
+    }
+
+    if let Some(path) = &options.load {
+        load_from_file(&mut gamestate, path)
+            .map_err(|error| format!("Could not load {}: {}", path, error))?;
+        place_ants(&mut gamestate, &options.ants)?;
+        return Ok(gamestate);
+    }
+
+    let rule_index = match options.rule.as_ref().unwrap_or(&RuleChoice::Index(0)) {
+        RuleChoice::Index(index) => {
+            if *index >= gamestate.get_rules().len() {
+                return Err(format!(
+                    "Rule index {} is out of range (0-{})",
+                    index,
+                    gamestate.get_rules().len() - 1
+                ));
+            }
+            *index
+        }
+        RuleChoice::Notation(turns) => match find_rule(&gamestate, turns) {
+            Some(index) => index,
+            None => {
+                let rule = Rule::new("Custom", turns, (0x000000, 0xFFFFFF)).map_err(|error| {
+                    format!(
+                        "\"{}\" is neither a rule name nor a valid rule: {}",
+                        turns, error
+                    )
+                })?;
+                match find_notation(&gamestate, &rule) {
+                    Some(index) => index,
+                    None => gamestate.add_rule(rule),
+                }
+            }
+        },
+    };
+    gamestate.select_rule(rule_index);
+
+    if let Some(size) = options.world {
+        let edge = options.edge.unwrap_or(Edge::Wrap);
+        let world = World::centered(size, edge)
+            .ok_or_else(|| format!("A world of {} x {} does not fit", size.0, size.1))?;
+        gamestate.set_world(Some(world));
+    }
+
+    place_ants(&mut gamestate, &options.ants)?;
+    Ok(gamestate)
+}
+
+fn place_ants(
+    gamestate: &mut Gamestate,
+    ants: &[((i32, i32), Option<String>)],
+) -> Result<(), String> {
+    let lattice = gamestate.get_rule().get_lattice();
+    for (position, heading) in ants {
+        let heading = match heading {
+            Some(heading) => parse_heading(heading, lattice)?,
+            None => 0,
+        };
+        if let Some(world) = gamestate.get_world()
+            && !world.contains(*position)
+        {
+            let (min_x, min_y, max_x, max_y) = world.get_bounds();
+            return Err(format!(
+                "Ant ({}, {}) is outside the world, which spans ({}, {}) to ({}, {})",
+                position.0, position.1, min_x, min_y, max_x, max_y
+            ));
+        }
+        gamestate.set_placement_heading(heading);
+        gamestate.add_ants(*position);
+    }
+    Ok(())
+}
+
+fn find_rule(gamestate: &Gamestate, name: &str) -> Option<usize> {
+    gamestate
+        .get_rules()
+        .iter()
+        .position(|rule| rule.get_name().eq_ignore_ascii_case(name))
+}
+
+// Known rule written with the same notation, so that a turn string such as RL picks Classic
+fn find_notation(gamestate: &Gamestate, rule: &Rule) -> Option<usize> {
+    gamestate
+        .get_rules()
+        .iter()
+        .position(|known| known.get_notation() == rule.get_notation())
+}
+
+// Advance the simulation, reporting why it stopped before the end
+pub fn run_steps(gamestate: &mut Gamestate, steps: u64) -> bool {
+    let performed = gamestate.step(steps);
+    if let Some(limit) = gamestate.take_step_limit() {
+        eprintln!(
+            "Stopped after {} of {} iterations, {}",
+            performed.separate_with_spaces(),
+            steps.separate_with_spaces(),
+            limit
+        );
+    }
+    performed == steps
+}
+
+// Advance the simulation in chunks of the recording interval, capturing a frame after each
+pub fn record_steps(
+    gamestate: &mut Gamestate,
+    steps: u64,
+    recorder: &mut Recorder,
+) -> Result<(), String> {
+    recorder.capture_if_due(gamestate)?;
+    let mut remaining = steps;
+    while remaining > 0 {
+        let chunk = remaining.min(recorder.get_interval());
+        let completed = run_steps(gamestate, chunk);
+        recorder.capture_if_due(gamestate)?;
+        if !completed {
+            break;
+        }
+        remaining -= chunk;
+    }
+    Ok(())
+}
+
+pub fn print_statistics(gamestate: &Gamestate) {
+    let rule = gamestate.get_rule();
+    println!("Rule: {} ({})", rule.get_name(), rule.get_notation());
+    println!(
+        "Iterations: {}",
+        gamestate.get_iteration().separate_with_spaces()
+    );
+    println!(
+        "Painted cells: {}",
+        gamestate.get_painted_cells().separate_with_spaces()
+    );
+    println!(
+        "Ants: {}",
+        gamestate.get_total_ants().separate_with_spaces()
+    );
+    match gamestate.get_bounding_box() {
+        Some((min_x, min_y, max_x, max_y)) => println!(
+            "Bounding box: ({}, {}) to ({}, {}) [{} x {}]",
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+            max_x as i64 - min_x as i64 + 1,
+            max_y as i64 - min_y as i64 + 1
+        ),
+        None => println!("Bounding box: empty"),
+    }
+    match gamestate.get_world() {
+        Some(world) => {
+            let (width, height) = world.get_size();
+            println!(
+                "World: {} x {}, {} edges",
+                width,
+                height,
+                world.get_edge().get_name()
+            );
+        }
+        None => println!("World: unbounded"),
+    }
+    match gamestate.get_cycle() {
+        Some(cycle) if cycle.is_highway() => println!(
+            "Highway: period {}, displacement ({}, {})",
+            cycle.period, cycle.displacement.0, cycle.displacement.1
+        ),
+        Some(cycle) => println!("Cycle: period {}", cycle.period),
+        None => println!("Highway: none detected"),
+    }
+    // State 0 is the unbounded background and is not counted
+    for (state, count) in gamestate.get_state_counts().iter().enumerate().skip(1) {
+        println!("State {}: {}", state, count.separate_with_spaces());
+    }
+}
+
+// Classify every rule up to the given length and write one CSV line per rule
+pub fn explore(options: &HeadlessOptions, max_length: usize) -> Result<(), String> {
+    let rules = enumerate_rules(&options.alphabet, max_length);
+    let mut report: Box<dyn Write> = match &options.report {
+        Some(path) => {
+            Box::new(BufWriter::new(File::create(path).map_err(|error| {
+                format!("Could not create {}: {}", path, error)
+            })?))
+        }
+        None => Box::new(io::stdout().lock()),
+    };
+    let write_error = |error: io::Error| format!("Could not write the report: {}", error);
+
+    writeln!(
+        report,
+        "rule,behaviour,period,displacement_x,displacement_y,painted_cells,width,height,growth,symmetry"
+    )
+    .map_err(write_error)?;
+
+    let mut totals = [0usize; 4];
+    for (index, notation) in rules.iter().enumerate() {
+        let exploration =
+            explore_rule(notation, options.steps).map_err(|error| error.to_string())?;
+        let (period, displacement) = match exploration.cycle {
+            Some(cycle) => (
+                cycle.period.to_string(),
+                (
+                    cycle.displacement.0.to_string(),
+                    cycle.displacement.1.to_string(),
+                ),
+            ),
+            None => (String::new(), (String::new(), String::new())),
+        };
+        writeln!(
+            report,
+            "{},{},{},{},{},{},{},{},{:.3},{:.3}",
+            exploration.notation,
+            exploration.behaviour,
+            period,
+            displacement.0,
+            displacement.1,
+            exploration.painted_cells,
+            exploration.size.0,
+            exploration.size.1,
+            exploration.growth,
+            exploration.symmetry
+        )
+        .map_err(write_error)?;
+
+        totals[match exploration.behaviour {
+            Behaviour::Highway => 0,
+            Behaviour::Bounded => 1,
+            Behaviour::Symmetric => 2,
+            Behaviour::Chaotic => 3,
+        }] += 1;
+        if (index + 1) % 100 == 0 {
+            eprintln!("Explored {}/{} rules", index + 1, rules.len());
+        }
+    }
+    report.flush().map_err(write_error)?;
+
+    eprintln!(
+        "Explored {} rules: {} highway, {} bounded, {} symmetric, {} chaotic",
+        rules.len(),
+        totals[0],
+        totals[1],
+        totals[2],
+        totals[3]
+    );
+    Ok(())
+}
+
+// Entry point of the headless mode, returns the process exit code
+pub fn run(args: &[String]) -> i32 {
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return 0;
+    }
+
+    let result = parse_arguments(args).and_then(|options| {
+        if let Some(max_length) = options.explore {
+            return explore(&options, max_length);
+        }
+        let mut gamestate = build_gamestate(&options)?;
+        match &options.record {
+            Some(path) => {
+                let region = match options.record_region {
+                    Some(cells) => RecordRegion::Fixed(cells),
+                    None => RecordRegion::Fit,
+                };
+                let mut recorder = Recorder::new(
+                    path,
+                    region,
+                    options.record_size,
+                    options.record_every,
+                    options.export_options.ants,
+                );
+                record_steps(&mut gamestate, options.steps, &mut recorder)?;
+                let frames = recorder.finish()?;
+                eprintln!("Recorded {} frames to {}", frames, path);
+            }
+            None => {
+                run_steps(&mut gamestate, options.steps);
+            }
+        }
+        if let Some(path) = &options.save {
+            save_to_file(&gamestate, path)
+                .map_err(|error| format!("Could not save {}: {}", path, error))?;
+        }
+        if let Some(path) = &options.export {
+            let (width, height) = export_png(&gamestate, path, &options.export_options)?;
+            eprintln!("Exported {} ({} x {} pixels)", path, width, height);
+        }
+        print_statistics(&gamestate);
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => 0,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            eprintln!("{}", USAGE);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arguments(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn parse_arguments_reads_the_rule_steps_and_ants() {
+        let options = parse_arguments(&arguments("--rule 2 --steps 500 --ant 3,-4")).unwrap();
+        assert!(matches!(options.rule, Some(RuleChoice::Index(2))));
+        assert_eq!(options.steps, 500);
+        assert_eq!(options.ants, vec![((3, -4), None)]);
+
+        let options = parse_arguments(&arguments("--rule RLLR")).unwrap();
+        assert!(matches!(options.rule, Some(RuleChoice::Notation(ref turns)) if turns == "RLLR"));
+        assert_eq!(options.ants, vec![((0, 0), None)]);
+    }
+
+    #[test]
+    fn parse_arguments_rejects_bad_input() {
+        assert!(parse_arguments(&arguments("--steps")).is_err());
+        assert!(parse_arguments(&arguments("--steps many")).is_err());
+        assert!(parse_arguments(&arguments("--frobnicate")).is_err());
+        assert!(parse_arguments(&arguments("--load run.lant --rule RL")).is_err());
+        assert!(parse_arguments(&arguments("--edge wall")).is_err());
+        assert!(parse_arguments(&arguments("--explore 4")).is_err());
+    }
+
+    #[test]
+    fn turn_strings_select_known_rules() {
+        let options = parse_arguments(&arguments("--rule RL")).unwrap();
+        let gamestate = build_gamestate(&options).unwrap();
+        let rule = &gamestate.get_rules()[gamestate.get_selected_rule()];
+        assert_eq!(rule.get_notation(), "RL");
+        assert_eq!(
+            gamestate
+                .get_rules()
+                .iter()
+                .filter(|rule| rule.get_notation() == "RL")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn headings_follow_the_lattice_of_the_rule() {
+        let headings = |line: &str| -> Result<Vec<u8>, String> {
+            let gamestate = build_gamestate(&parse_arguments(&arguments(line))?)?;
+            Ok(gamestate.get_ants().map(|ant| ant.heading).collect())
+        };
+        assert_eq!(
+            headings("--rule RL --ant 0,0,s --ant 1,0,left --ant 2,0,W --ant 3,0,1").unwrap(),
+            vec![2, 3, 3, 1]
+        );
+        assert_eq!(
+            headings("--rule L2NNL1L2L1 --ant 0,0,s --ant 1,0,nw --ant 2,0,5").unwrap(),
+            vec![3, 5, 5]
+        );
+        assert!(headings("--rule RL --ant 0,0,sw").is_err());
+        assert!(headings("--rule RL --ant 0,0,4").is_err());
+        assert!(headings("--rule L2NNL1L2L1 --ant 0,0,left").is_err());
+        assert!(headings("--rule L2NNL1L2L1 --ant 0,0,6").is_err());
+    }
+}
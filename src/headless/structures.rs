@@ -0,0 +1,75 @@
+use crate::export::structures::ExportOptions;
+use langton_engine::gamemodes::langton::Turn;
+use langton_engine::world::Edge;
+
+/// How the rule of a headless run is chosen
+pub enum RuleChoice {
+    // Index into the built-in rule list
+    Index(usize),
+    // Rule name, or an explicit turn string such as "RLLR"
+    Notation(String),
+}
+
+/// Options of a headless run, parsed from the command line
+pub struct HeadlessOptions {
+    pub rule: Option<RuleChoice>,
+    pub steps: u64,
+    // Position and heading of the ants placed, the heading is read once the lattice of the
+    // rule is known
+    pub ants: Vec<((i32, i32), Option<String>)>,
+    // Threads stepping large ant populations, 0 for one per core
+    pub threads: usize,
+    // Skip whole periods of a highway instead of stepping through them
+    pub fast_forward: bool,
+    // Size of the world centered on the origin, the grid is unbounded when missing
+    pub world: Option<(u32, u32)>,
+    pub edge: Option<Edge>,
+    // Rule file merged with the built-in rules
+    pub rules: Option<String>,
+    // Snapshot to resume from instead of starting a new run
+    pub load: Option<String>,
+    // Snapshot written once the run is over
+    pub save: Option<String>,
+    // Longest turn string classified instead of a single run
+    pub explore: Option<usize>,
+    // Turns of the explored rules
+    pub alphabet: Vec<Turn>,
+    // CSV report of the exploration, printed when missing
+    pub report: Option<String>,
+    // PNG image of the whole pattern written once the run is over
+    pub export: Option<String>,
+    pub export_options: ExportOptions,
+    // Animated GIF, or directory of PNG frames, captured during the run
+    pub record: Option<String>,
+    // Iterations between two frames
+    pub record_every: u64,
+    // Cells shown by the frames, the whole pattern when missing
+    pub record_region: Option<(i32, i32, i32, i32)>,
+    pub record_size: (u32, u32),
+}
+
+impl HeadlessOptions {
+    pub fn new() -> Self {
+        HeadlessOptions {
+            rule: None,
+            rules: None,
+            steps: 0,
+            ants: Vec::new(),
+            threads: 0,
+            fast_forward: true,
+            world: None,
+            edge: None,
+            load: None,
+            save: None,
+            explore: None,
+            alphabet: vec![Turn::Right, Turn::Left],
+            report: None,
+            export: None,
+            export_options: ExportOptions::new(),
+            record: None,
+            record_every: 100,
+            record_region: None,
+            record_size: (512, 512),
+        }
+    }
+}
@@ -1,116 +1,539 @@
-use crate::gamemodes::langton::Gamestate;
-use crate::objects::structures::CCamera;
+use crate::config::functions::{KEYBINDING_FILE, MODIFIER_KEY, check_bindable, save_keybindings};
+use crate::config::structures::{Action, Keybindings, KeyboardPreset};
+use crate::export::functions::export_png;
+use crate::export::structures::{ExportOptions, RecordRegion, Recorder};
+use crate::objects::structures::{
+    Brush, CCamera, Clipboard, GamemodeMenu, KeybindingMenu, MessageLog, Tool,
+};
+use langton_engine::gamemodes::Gamemode;
+use langton_engine::history::History;
+use langton_engine::pattern::{MAX_PATTERN_CELLS, Pattern};
+use langton_engine::save::{load_from_file, save_to_file};
+use langton_engine::world::{Edge, World};
 use macroquad::prelude::*;
+use std::path::Path;
 
-pub fn handle_input(camera: &mut CCamera, gamestate: &mut Gamestate) {
-    // Handle zoom with mouse wheel
-    camera.set_zoom(1.0 + mouse_wheel().1 * 0.1, mouse_position());
+// Snapshot written and read by the save and load keys
+const SAVE_FILE: &str = "langton.save";
+// Image of the whole pattern written by the export key
+const EXPORT_FILE: &str = "langton.png";
+const EXPORT_CELL_PIXELS: u32 = 4;
+// Animation written by the record key, the auto-fitting recording uses square frames
+const RECORD_FILE: &str = "langton.gif";
+const RECORD_FIT_SIZE: (u32, u32) = (512, 512);
+// Zoom change per frame while a zoom key is held
+const KEY_ZOOM_STEP: f32 = 0.02;
+// World created around the origin when nothing is selected
+const DEFAULT_WORLD_SIZE: (u32, u32) = (256, 256);
+
+// Number keys picking an entry of the gamemode menu
+const MENU_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+// Toggle the gamemode menu and return the gamemode picked in it
+pub fn handle_menu_input(
+    menu: &mut GamemodeMenu,
+    keybindings: &Keybindings,
+    gamemode_count: usize,
+) -> Option<usize> {
+    if keybindings.is_pressed(Action::GamemodeMenu) {
+        menu.toggle();
+    }
+    if !menu.is_open() {
+        return None;
+    }
+    for (index, key) in MENU_KEYS.iter().take(gamemode_count).enumerate() {
+        if is_key_pressed(*key) {
+            menu.close();
+            return Some(index);
+        }
+    }
+    None
+}
+
+// Open and close the keybinding screen and rebind the actions picked in it.
+// Every change is written to the keybinding file.
+pub fn handle_keybinding_input(
+    menu: &mut KeybindingMenu,
+    keybindings: &mut Keybindings,
+    messages: &mut MessageLog,
+) {
+    if menu.is_capturing() {
+        let Some(key) = get_last_key_pressed() else {
+            return;
+        };
+        menu.set_capturing(false);
+        if key == KeyCode::Escape {
+            return;
+        }
+        let action = keybindings.get_bindings()[menu.get_selected()].0;
+        match check_bindable(key) {
+            Ok(()) => keybindings.bind(action, Some(key)),
+            Err(error) => {
+                messages.push(&format!("Could not bind {}: {}", action.get_name(), error));
+                return;
+            }
+        }
+    } else {
+        if keybindings.is_pressed(Action::KeybindingMenu)
+            || (menu.is_open() && is_key_pressed(KeyCode::Escape))
+        {
+            menu.toggle();
+        }
+        if !menu.is_open() {
+            return;
+        }
+        let action_count = keybindings.get_bindings().len();
+        if is_key_pressed(KeyCode::Up) {
+            menu.move_selection(-1, action_count);
+        }
+        if is_key_pressed(KeyCode::Down) {
+            menu.move_selection(1, action_count);
+        }
+        if is_key_pressed(KeyCode::Enter) {
+            menu.set_capturing(true);
+        }
+
+        let presets = KeyboardPreset::all();
+        let preset = presets
+            .iter()
+            .position(|preset| *preset == keybindings.get_preset())
+            .unwrap_or(0);
+        let changed = if is_key_pressed(KeyCode::Delete) {
+            let action = keybindings.get_bindings()[menu.get_selected()].0;
+            keybindings.bind(action, None);
+            true
+        } else if is_key_pressed(KeyCode::Right) {
+            *keybindings = Keybindings::new(presets[(preset + 1) % presets.len()]);
+            true
+        } else if is_key_pressed(KeyCode::Left) {
+            *keybindings = Keybindings::new(presets[(preset + presets.len() - 1) % presets.len()]);
+            true
+        } else {
+            false
+        };
+        if !changed {
+            return;
+        }
+    }
+
+    if let Err(error) = save_keybindings(keybindings, Path::new(KEYBINDING_FILE)) {
+        messages.push(&error);
+    }
+}
+
+// Undo and redo with the modifier held, only Langton's ants keep a history
+pub fn handle_history_input(
+    gamestate: &mut dyn Gamemode,
+    history: &mut History,
+    keybindings: &Keybindings,
+    messages: &mut MessageLog,
+) {
+    // Journaling the simulation slows it down, it is only done on request
+    if keybindings.is_pressed(Action::SimulationHistory) {
+        let recording = !history.get_simulation_recording();
+        history.set_simulation_recording(recording);
+        messages.push(if recording {
+            "Undo goes back through simulation steps"
+        } else {
+            "Undo only goes back through edits made while paused"
+        });
+    }
+    if !is_key_down(MODIFIER_KEY) {
+        return;
+    }
+    let undo = is_key_pressed(KeyCode::Z);
+    let redo = is_key_pressed(KeyCode::Y);
+    if !undo && !redo {
+        return;
+    }
+    let mode_name = gamestate.get_mode_name().to_string();
+    match gamestate.as_langton_mut() {
+        Some(langton) => {
+            if undo && !history.undo(langton) {
+                messages.push("Nothing to undo");
+            }
+            if redo && !history.redo(langton) {
+                messages.push("Nothing to redo");
+            }
+        }
+        None => messages.push(&format!("Undo is not available in {}", mode_name)),
+    }
+}
+
+// Go back by the current speed within the time of a frame, returning the number of iterations
+// undone
+pub fn step_back(gamestate: &mut dyn Gamemode, messages: &mut MessageLog) -> u64 {
+    let mode_name = gamestate.get_mode_name().to_string();
+    let speed = gamestate.get_speed();
+    match gamestate.as_langton_mut() {
+        Some(langton) => match langton.step_back_frame(speed) {
+            Ok(iterations) => iterations,
+            Err(error) => {
+                messages.push(&format!("Cannot step back: {}", error));
+                0
+            }
+        },
+        None => {
+            messages.push(&format!(
+                "Reverse stepping is not available in {}",
+                mode_name
+            ));
+            0
+        }
+    }
+}
+
+// Close the current recording, if any, and report the number of frames written
+pub fn stop_recording(recorder: &mut Option<Recorder>, messages: &mut MessageLog) {
+    if let Some(recorder) = recorder.take() {
+        let path = recorder.get_path().to_string();
+        match recorder.finish() {
+            Ok(frames) => messages.push(&format!("Recorded {} frames to {}", frames, path)),
+            Err(error) => messages.push(&format!("Could not record: {}", error)),
+        }
+    }
+}
+
+pub fn handle_input(
+    camera: &mut CCamera,
+    gamestate: &mut dyn Gamemode,
+    keybindings: &Keybindings,
+    brush: &mut Brush,
+    clipboard: &mut Clipboard,
+    recorder: &mut Option<Recorder>,
+    messages: &mut MessageLog,
+) {
+    // Handle zoom with mouse wheel, the zoom keys zoom around the center of the screen.
+    // With the heading modifier held the wheel turns the heading of the placed ants instead.
+    let wheel = mouse_wheel().1;
+    if is_key_down(MODIFIER_KEY) {
+        if let Some(langton) = gamestate.as_langton_mut()
+            && wheel != 0.0
+        {
+            langton.rotate_placement_heading(wheel.signum() as isize);
+        }
+    } else {
+        camera.set_zoom(1.0 + wheel * 0.1, mouse_position());
+    }
+    let screen_center = (screen_width() / 2.0, screen_height() / 2.0);
+    if keybindings.is_down(Action::ZoomIn) {
+        camera.set_zoom(1.0 + KEY_ZOOM_STEP, screen_center);
+    }
+    if keybindings.is_down(Action::ZoomOut) {
+        camera.set_zoom(1.0 / (1.0 + KEY_ZOOM_STEP), screen_center);
+    }
 
     // Camera movement
-    if is_key_down(KeyCode::Z) {
+    if keybindings.is_down(Action::PanUp) {
         camera.move_camera(0.0, -1.0);
     }
-    if is_key_down(KeyCode::S) {
+    if keybindings.is_down(Action::PanDown) {
         camera.move_camera(0.0, 1.0);
     }
-    if is_key_down(KeyCode::Q) {
+    if keybindings.is_down(Action::PanLeft) {
         camera.move_camera(-1.0, 0.0);
     }
-    if is_key_down(KeyCode::D) {
+    if keybindings.is_down(Action::PanRight) {
         camera.move_camera(1.0, 0.0);
     }
 
-    if is_key_pressed(KeyCode::Up) && gamestate.get_cursor_y() < 10 {
-        gamestate.increment_cursor_y(1);
+    let (cursor_x, cursor_y) = gamestate.get_cursor_dimensions();
+    if keybindings.is_pressed(Action::CursorTaller) && cursor_y < 10 {
+        gamestate.set_cursor_dimensions((cursor_x, cursor_y + 1));
     }
-    if is_key_pressed(KeyCode::Down) && gamestate.get_cursor_y() > 1 {
-        gamestate.decrement_cursor_y(1);
+    if keybindings.is_pressed(Action::CursorShorter) && cursor_y > 1 {
+        gamestate.set_cursor_dimensions((cursor_x, cursor_y - 1));
     }
-    if is_key_pressed(KeyCode::Left) && gamestate.get_cursor_x() > 1 {
-        gamestate.decrement_cursor_x(1);
+    if keybindings.is_pressed(Action::CursorNarrower) && cursor_x > 1 {
+        gamestate.set_cursor_dimensions((cursor_x - 1, cursor_y));
     }
-    if is_key_pressed(KeyCode::Right) && gamestate.get_cursor_x() < 10 {
-        gamestate.increment_cursor_x(1);
+    if keybindings.is_pressed(Action::CursorWider) && cursor_x < 10 {
+        gamestate.set_cursor_dimensions((cursor_x + 1, cursor_y));
     }
 
-    // Gameplay
-    // 0->UP 1->RIGHT 2->DOWN 3->LEFT
-    if is_mouse_button_pressed(MouseButton::Left)
-        || (is_key_down(KeyCode::LeftShift)) && (is_mouse_button_down(MouseButton::Left))
+    // Tools
+    let state_count = gamestate.get_state_colors().len();
+    if keybindings.is_pressed(Action::NextTool) {
+        brush.set_tool(brush.get_tool().next());
+    }
+    if keybindings.is_pressed(Action::NextPaintState) {
+        brush.next_paint_state(state_count);
+    }
+    if keybindings.is_pressed(Action::RotateHeading)
+        && let Some(langton) = gamestate.as_langton_mut()
     {
-        gamestate
-            .add_ants(camera.screen_to_grid_position(mouse_position(), camera.get_cell_size()));
+        langton.rotate_placement_heading(1);
     }
 
-    if is_key_pressed(KeyCode::R) {
-        gamestate.clear_ants();
+    // Gameplay
+    // Placing and filling act once per click, painting and erasing follow the mouse
+    let position = camera.screen_to_grid_position(mouse_position(), camera.get_cell_size());
+    let clicked = is_mouse_button_pressed(MouseButton::Left);
+    let held = is_mouse_button_down(MouseButton::Left);
+    match brush.get_tool() {
+        Tool::Place => {
+            if clicked || (is_key_down(KeyCode::LeftShift) && held) {
+                gamestate.place(position);
+            }
+        }
+        Tool::Paint => {
+            if held {
+                gamestate.paint(position, brush.get_paint_state(state_count));
+            }
+        }
+        Tool::Erase => {
+            if held {
+                gamestate.paint(position, 0);
+            }
+        }
+        Tool::Fill => {
+            if clicked
+                && gamestate
+                    .flood_fill(position, brush.get_paint_state(state_count))
+                    .is_none()
+            {
+                messages.push("Nothing filled, the area is not enclosed by painted cells");
+            }
+        }
+        Tool::Select => {
+            if clicked {
+                clipboard.start_selection(position);
+            } else if held {
+                clipboard.extend_selection(position);
+            } else {
+                clipboard.end_selection();
+            }
+        }
+        Tool::Paste => {
+            if clicked && let Some(pattern) = clipboard.get_pattern() {
+                pattern.paste(gamestate, position);
+            }
+        }
     }
 
-    if is_key_pressed(KeyCode::T) {
-        gamestate.clear_grid();
+    // Clipboard
+    if keybindings.is_pressed(Action::CopySelection)
+        && let Some(selection) = clipboard.get_selection()
+    {
+        match Pattern::copy(gamestate, selection) {
+            Some(pattern) => {
+                let (width, height) = pattern.get_size();
+                messages.push(&format!("Copied {} x {} cells", width, height));
+                clipboard.set_pattern(pattern);
+                brush.set_tool(Tool::Paste);
+            }
+            None => messages.push(&format!(
+                "The selection is too large to copy, {} cells at most",
+                MAX_PATTERN_CELLS
+            )),
+        }
+    }
+    if keybindings.is_pressed(Action::RotatePattern)
+        && let Some(pattern) = clipboard.get_pattern_mut()
+    {
+        pattern.rotate();
+    }
+    if keybindings.is_pressed(Action::MirrorPattern)
+        && let Some(pattern) = clipboard.get_pattern_mut()
+    {
+        pattern.mirror();
     }
 
-    if is_key_pressed(KeyCode::Space) {
-        gamestate.invert_pause_state();
+    // The world is taken from the selection, its edges change at every press until it is removed
+    if keybindings.is_pressed(Action::CycleWorld) {
+        let mode_name = gamestate.get_mode_name().to_string();
+        match gamestate.as_langton_mut() {
+            Some(langton) => {
+                let world = match langton.get_world() {
+                    None => {
+                        let world = match clipboard.get_selection() {
+                            Some((min_x, min_y, max_x, max_y)) => World::new(
+                                (min_x, min_y),
+                                (
+                                    (max_x as i64 - min_x as i64 + 1) as u32,
+                                    (max_y as i64 - min_y as i64 + 1) as u32,
+                                ),
+                                Edge::Wrap,
+                            ),
+                            None => World::centered(DEFAULT_WORLD_SIZE, Edge::Wrap),
+                        };
+                        if world.is_none() {
+                            messages.push("The selection is too large for a world");
+                        }
+                        world
+                    }
+                    Some(world) => match world.get_edge() {
+                        Edge::Wrap => Some(world.with_edge(Edge::Wall)),
+                        Edge::Wall => Some(world.with_edge(Edge::Kill)),
+                        Edge::Kill => None,
+                    },
+                };
+                match world {
+                    Some(world) => {
+                        let (width, height) = world.get_size();
+                        messages.push(&format!(
+                            "World of {} x {} cells with {} edges",
+                            width,
+                            height,
+                            world.get_edge().get_name()
+                        ));
+                        langton.set_world(Some(world));
+                    }
+                    None if langton.get_world().is_some() => {
+                        messages.push("Unbounded grid");
+                        langton.set_world(None);
+                    }
+                    None => {}
+                }
+            }
+            None => messages.push(&format!("Worlds are not available in {}", mode_name)),
+        }
     }
 
-    if is_key_pressed(KeyCode::F) {
-        gamestate.set_pause_state(true);
+    // The right button removes the ants under the brush, whatever the tool
+    if is_mouse_button_down(MouseButton::Right)
+        && let Some(langton) = gamestate.as_langton_mut()
+    {
+        langton.remove_ants(position);
     }
 
-    if is_key_pressed(KeyCode::F1) {
-        gamestate.select_rule(0);
-        gamestate.reset();
+    if keybindings.is_pressed(Action::ClearAnts)
+        && let Some(langton) = gamestate.as_langton_mut()
+    {
+        langton.clear_ants();
     }
-    if is_key_pressed(KeyCode::F2) {
-        gamestate.select_rule(1);
-        gamestate.reset();
+
+    if keybindings.is_pressed(Action::ClearGrid) {
+        gamestate.clear_grid();
     }
-    if is_key_pressed(KeyCode::F3) {
-        gamestate.select_rule(2);
-        gamestate.reset();
+
+    if keybindings.is_pressed(Action::Pause) {
+        gamestate.invert_pause_state();
     }
-    if is_key_pressed(KeyCode::F4) {
-        gamestate.select_rule(3);
-        gamestate.reset();
+
+    if keybindings.is_pressed(Action::Step) || keybindings.is_pressed(Action::StepBack) {
+        gamestate.set_pause_state(true);
     }
-    if is_key_pressed(KeyCode::F5) {
-        gamestate.select_rule(4);
-        gamestate.reset();
+
+    // Only rules whose transitions can be told apart run backward
+    if keybindings.is_pressed(Action::Reverse) {
+        let mode_name = gamestate.get_mode_name().to_string();
+        match gamestate.as_langton_mut() {
+            Some(langton) if langton.get_reverse_state() => langton.set_reverse_state(false),
+            Some(langton) => match langton.check_step_back() {
+                Ok(()) => langton.set_reverse_state(true),
+                Err(error) => messages.push(&format!("Cannot run backward: {}", error)),
+            },
+            None => messages.push(&format!(
+                "Reverse stepping is not available in {}",
+                mode_name
+            )),
+        }
     }
-    if is_key_pressed(KeyCode::F6) {
-        gamestate.select_rule(5);
-        gamestate.reset();
+
+    // Snapshots only hold the state of Langton's ants
+    if keybindings.is_pressed(Action::Save) {
+        match gamestate.as_langton() {
+            Some(langton) => match save_to_file(langton, SAVE_FILE) {
+                Ok(()) => messages.push(&format!("Saved the simulation to {}", SAVE_FILE)),
+                Err(error) => messages.push(&format!("Could not save {}: {}", SAVE_FILE, error)),
+            },
+            None => messages.push(&format!(
+                "Snapshots are not available in {}",
+                gamestate.get_mode_name()
+            )),
+        }
     }
-    if is_key_pressed(KeyCode::F7) {
-        gamestate.select_rule(6);
-        gamestate.reset();
+    if keybindings.is_pressed(Action::Load) {
+        let mode_name = gamestate.get_mode_name().to_string();
+        match gamestate.as_langton_mut() {
+            Some(langton) => match load_from_file(langton, SAVE_FILE) {
+                Ok(()) => messages.push(&format!("Loaded the simulation from {}", SAVE_FILE)),
+                Err(error) => messages.push(&format!("Could not load {}: {}", SAVE_FILE, error)),
+            },
+            None => messages.push(&format!("Snapshots are not available in {}", mode_name)),
+        }
     }
-    if is_key_pressed(KeyCode::F8) {
-        gamestate.select_rule(7);
-        gamestate.reset();
+
+    if keybindings.is_pressed(Action::Export) {
+        let options = ExportOptions {
+            cell_pixels: EXPORT_CELL_PIXELS,
+            ants: true,
+        };
+        match export_png(gamestate, EXPORT_FILE, &options) {
+            Ok((width, height)) => messages.push(&format!(
+                "Exported the pattern to {} ({} x {} pixels)",
+                EXPORT_FILE, width, height
+            )),
+            Err(error) => messages.push(&format!("Could not export: {}", error)),
+        }
     }
-    if is_key_pressed(KeyCode::F9) {
-        gamestate.select_rule(8);
-        gamestate.reset();
+
+    // Frames are captured every time the simulation advances by the speed it had when the recording started
+    if keybindings.is_pressed(Action::Record) {
+        if recorder.is_some() {
+            stop_recording(recorder, messages);
+        } else {
+            let interval = gamestate.get_speed().max(1);
+            *recorder = Some(if is_key_down(KeyCode::LeftShift) {
+                messages.push(&format!("Recording the whole pattern to {}", RECORD_FILE));
+                Recorder::new(
+                    RECORD_FILE,
+                    RecordRegion::Fit,
+                    RECORD_FIT_SIZE,
+                    interval,
+                    true,
+                )
+            } else {
+                messages.push(&format!("Recording the screen to {}", RECORD_FILE));
+                // Half the screen resolution keeps the encoding time of a frame low
+                let frame_size = (
+                    (screen_width() / 2.0).max(1.0) as u32,
+                    (screen_height() / 2.0).max(1.0) as u32,
+                );
+                Recorder::new(
+                    RECORD_FILE,
+                    RecordRegion::Fixed(camera.get_visible_range(camera.get_cell_size() as f32)),
+                    frame_size,
+                    interval,
+                    true,
+                )
+            });
+        }
     }
-    if is_key_pressed(KeyCode::F10) {
-        gamestate.select_rule(9);
-        gamestate.reset();
+
+    for rule in 1..=12 {
+        if keybindings.is_pressed(Action::SelectRule(rule)) {
+            gamestate.select_rule(rule as usize - 1);
+            gamestate.reset();
+        }
     }
-    if is_key_pressed(KeyCode::F11) {
-        gamestate.select_rule(10);
+
+    // Cycle through every rule, including those beyond the function keys
+    let rule_count = gamestate.get_rule_names().len();
+    if keybindings.is_pressed(Action::NextRule) {
+        gamestate.select_rule((gamestate.get_selected_rule() + 1) % rule_count);
         gamestate.reset();
     }
-    if is_key_pressed(KeyCode::F12) {
-        gamestate.select_rule(11);
+    if keybindings.is_pressed(Action::PreviousRule) {
+        gamestate.select_rule((gamestate.get_selected_rule() + rule_count - 1) % rule_count);
         gamestate.reset();
     }
 
-    if is_key_pressed(KeyCode::J) {
-        gamestate.set_speed((gamestate.get_speed() as f32 * 2.0) as u32);
+    if keybindings.is_pressed(Action::SpeedUp) {
+        gamestate.set_speed(gamestate.get_speed().saturating_mul(2));
     }
-    if is_key_pressed(KeyCode::K) {
+    if keybindings.is_pressed(Action::ResetSpeed) {
         gamestate.set_speed(1);
     }
 }
@@ -3,14 +3,37 @@ use macroquad::prelude::*;
 mod objects;
 use objects::structures::*;
 
-mod gamemodes;
-use gamemodes::langton::Gamestate;
+use langton_engine::gamemodes::Gamemode;
+use langton_engine::gamemodes::langton::Gamestate;
+use langton_engine::gamemodes::life::Life;
+use langton_engine::history::{ChangeKind, History};
 
 mod input;
-use input::functions::handle_input;
+use input::functions::{
+    handle_history_input, handle_input, handle_keybinding_input, handle_menu_input, step_back,
+    stop_recording,
+};
 
-#[macroquad::main("Langton's Ant")]
-async fn main() {
+mod headless;
+
+mod config;
+
+mod export;
+use config::functions::{load_default_keybindings, load_default_rule_file};
+use config::structures::Action;
+use export::structures::Recorder;
+
+fn main() {
+    // Any argument switches to the command-line mode, which never opens a window
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(headless::functions::run(&args));
+    }
+
+    macroquad::Window::new("Langton's Ant", window_main());
+}
+
+async fn window_main() {
     // Fullscreen
     macroquad::window::set_fullscreen(true);
     while screen_width() == 800.0 || screen_height() == 600.0 {
@@ -18,23 +41,113 @@ async fn main() {
     }
 
     // Structures
-    let mut game_data = Gamestate::new();
     let mut camera = CCamera::new();
     let mut render = LangtonRenderer::new();
+    let mut messages = MessageLog::new();
+    let mut menu = GamemodeMenu::new();
+    let mut keybinding_menu = KeybindingMenu::new();
+    let mut brush = Brush::new();
+    let mut clipboard = Clipboard::new();
+
+    let (mut keybindings, keybinding_messages) = load_default_keybindings();
+    for message in keybinding_messages {
+        messages.push(&message);
+    }
+
+    let mut langton = Gamestate::new();
+    for message in load_default_rule_file(&mut langton) {
+        messages.push(&message);
+    }
+
+    // Every gamemode keeps its state while another one is shown
+    let mut gamemodes: Vec<Box<dyn Gamemode>> = vec![Box::new(langton), Box::new(Life::new())];
+    let mut active = 0;
+    let mut recorder: Option<Recorder> = None;
+    // Edits and simulation steps of Langton's ants
+    let mut history = History::new();
 
     loop {
-        handle_input(&mut camera, &mut game_data);
+        if !menu.is_open() {
+            handle_keybinding_input(&mut keybinding_menu, &mut keybindings, &mut messages);
+        }
+        if !keybinding_menu.is_open()
+            && let Some(choice) = handle_menu_input(&mut menu, &keybindings, gamemodes.len())
+        {
+            // The brush follows the player across gamemodes
+            let cursor = gamemodes[active].get_cursor_dimensions();
+            gamemodes[choice].set_cursor_dimensions(cursor);
+            active = choice;
+            render.invalidate();
+            stop_recording(&mut recorder, &mut messages);
+        }
+        let game_data = gamemodes[active].as_mut();
+
+        // Follow the lattice of the selected rule
+        camera.set_lattice(game_data.get_lattice());
+
+        let in_menu = menu.is_open() || keybinding_menu.is_open();
+        if !in_menu {
+            if let Some(langton) = game_data.as_langton_mut() {
+                history.begin(langton, ChangeKind::Edit);
+            }
+            handle_input(
+                &mut camera,
+                game_data,
+                &keybindings,
+                &mut brush,
+                &mut clipboard,
+                &mut recorder,
+                &mut messages,
+            );
+            if let Some(langton) = game_data.as_langton_mut() {
+                history.commit(langton, ChangeKind::Edit);
+            }
+            handle_history_input(game_data, &mut history, &keybindings, &mut messages);
+        }
 
-        game_data.update(game_data.get_speed());
+        // Single stepping advances the simulation even while paused
+        if let Some(langton) = game_data.as_langton_mut() {
+            history.begin(langton, ChangeKind::Simulation);
+        }
+        let iterations = if !in_menu && keybindings.is_pressed(Action::Step) {
+            game_data.step_frame(game_data.get_speed())
+        } else if !in_menu && keybindings.is_pressed(Action::StepBack) {
+            step_back(game_data, &mut messages)
+        } else {
+            game_data.update(game_data.get_speed())
+        };
+        if let Some(langton) = game_data.as_langton_mut() {
+            history.commit(langton, ChangeKind::Simulation);
+            if let Some(error) = langton.take_reverse_error() {
+                messages.push(&format!("Stopped running backward: {}", error));
+            }
+            if let Some(limit) = langton.take_step_limit() {
+                messages.push(&format!("Paused, {}", limit));
+            }
+            if iterations > 0 && !history.is_journaling(ChangeKind::Simulation) {
+                history.forget_simulation();
+            }
+        }
+        render.set_update_speed(iterations.saturating_mul(get_fps() as u64));
+
+        if let Some(active_recorder) = &mut recorder
+            && let Err(error) = active_recorder.capture_if_due(game_data)
+        {
+            messages.push(&format!("Could not record: {}", error));
+            recorder = None;
+        }
 
         clear_background(Color::from_hex(0x666666));
 
         // Stop rendering for performance gains
         if !is_key_down(KeyCode::Tab) {
-            render.render(&camera, &game_data);
+            render.render(&camera, game_data, &brush, &clipboard);
         }
 
-        render.draw_texts(&camera, &game_data);
+        render.draw_texts(&camera, game_data, &brush, recorder.as_ref());
+        menu.draw(&gamemodes, active);
+        keybinding_menu.draw(&keybindings);
+        messages.draw();
 
         next_frame().await;
     }
@@ -1,37 +1,318 @@
 use macroquad::prelude::*;
 
 use crate::objects::structures::CCamera;
+use langton_engine::gamemodes::langton::Lattice;
+use langton_engine::grid::{CHUNK_SIZE, Chunk, ChunkedGrid};
+use langton_engine::pattern::Pattern;
+use langton_engine::world::World;
 
 pub fn draw_cell_grid(camera: &CCamera, start_x: i32, start_y: i32, end_x: i32, end_y: i32) {
     // Draw the grid when relevant
     if camera.get_zoom() > 0.5 {
         // Draw vertical lines
         for grid_x in start_x..=end_x {
-            let (screen_x, _) =
-                camera.grid_to_screen_position((grid_x, 0), camera.get_cell_size() as usize);
+            let (screen_x, _) = camera.grid_to_screen_position((grid_x, 0), camera.get_cell_size());
             draw_line(screen_x, 0.0, screen_x, screen_height(), 1.0, GRAY);
         }
 
         // Draw horizontal lines
         for grid_y in start_y..=end_y {
-            let (_, screen_y) =
-                camera.grid_to_screen_position((0, grid_y), camera.get_cell_size() as usize);
+            let (_, screen_y) = camera.grid_to_screen_position((0, grid_y), camera.get_cell_size());
             draw_line(0.0, screen_y, screen_width(), screen_y, 1.0, GRAY);
         }
     }
 }
 
-pub fn draw_cursor(camera: &CCamera, x: u8, y: u8) {
-    let (mouse_x, mouse_y) = camera.grid_to_screen_position(
-        camera.screen_to_grid_position(mouse_position(), camera.get_cell_size()),
-        camera.get_cell_size(),
+// Height of a flat-topped hexagon relative to its width
+pub const HEX_HEIGHT_RATIO: f32 = 0.866_025_4;
+
+// World position of the top left corner of the box around a hexagonal cell,
+// the cell size is the width of a hexagon and columns overlap by a quarter of it
+pub fn hex_axial_to_world(axial: (i32, i32), cell_size: f32) -> (f32, f32) {
+    let (q, r) = axial;
+    (
+        cell_size * 0.75 * q as f32,
+        cell_size * HEX_HEIGHT_RATIO * (r as f32 + q as f32 / 2.0),
+    )
+}
+
+// Axial coordinates of the hexagonal cell containing a world position
+pub fn hex_world_to_axial(world: (f32, f32), cell_size: f32) -> (i32, i32) {
+    // Position relative to the center of the cell (0, 0)
+    let radius = cell_size / 2.0;
+    let x = world.0 - radius;
+    let y = world.1 - cell_size * HEX_HEIGHT_RATIO / 2.0;
+
+    let q = (2.0 / 3.0 * x) / radius;
+    let r = (-x / 3.0 + 3.0_f32.sqrt() / 3.0 * y) / radius;
+
+    // Round in cube coordinates, then fix the component with the largest error
+    let s = -q - r;
+    let (mut rounded_q, mut rounded_r, rounded_s) = (q.round(), r.round(), s.round());
+    let (error_q, error_r, error_s) = (
+        (rounded_q - q).abs(),
+        (rounded_r - r).abs(),
+        (rounded_s - s).abs(),
     );
+    if error_q > error_r && error_q > error_s {
+        rounded_q = -rounded_r - rounded_s;
+    } else if error_r > error_s {
+        rounded_r = -rounded_q - rounded_s;
+    }
+    (rounded_q as i32, rounded_r as i32)
+}
+
+pub fn draw_cursor(camera: &CCamera, x: u8, y: u8) {
+    let cell_size = camera.get_cell_size();
+    let position = camera.screen_to_grid_position(mouse_position(), cell_size);
+
+    // Outline every hexagon of the brush
+    if camera.get_lattice() == Lattice::Hexagonal {
+        for offset_x in 0..x as i32 {
+            for offset_y in 0..y as i32 {
+                let (center_x, center_y) = camera.grid_to_screen_center(
+                    (position.0 + offset_x, position.1 + offset_y),
+                    cell_size,
+                );
+                let radius = camera.get_scaled_cell_size() / 2.0;
+                draw_hexagon(center_x, center_y, radius, 3.0, false, RED, BLANK);
+            }
+        }
+        return;
+    }
+
+    let (mouse_x, mouse_y) = camera.grid_to_screen_position(position, cell_size);
     draw_rectangle_lines(
-        mouse_x as f32,
-        mouse_y as f32,
+        mouse_x,
+        mouse_y,
         x as f32 * camera.get_scaled_cell_size(),
         y as f32 * camera.get_scaled_cell_size(),
         6.0,
         RED,
     );
 }
+
+pub fn draw_selection(camera: &CCamera, region: (i32, i32, i32, i32)) {
+    draw_region_outline(camera, region, SKYBLUE);
+}
+
+// Border of the world the ants are kept in
+pub fn draw_world_border(camera: &CCamera, world: &World) {
+    draw_region_outline(camera, world.get_bounds(), ORANGE);
+}
+
+// Outline an inclusive rectangle of cells, a parallelogram joining the corner cells on hexagons
+fn draw_region_outline(camera: &CCamera, region: (i32, i32, i32, i32), color: Color) {
+    let cell_size = camera.get_cell_size();
+    let (min_x, min_y, max_x, max_y) = region;
+    if camera.get_lattice() == Lattice::Hexagonal {
+        let corners = [
+            (min_x, min_y),
+            (max_x, min_y),
+            (max_x, max_y),
+            (min_x, max_y),
+        ]
+        .map(|corner| camera.grid_to_screen_center(corner, cell_size));
+        for (index, (start_x, start_y)) in corners.iter().enumerate() {
+            let (end_x, end_y) = corners[(index + 1) % corners.len()];
+            draw_line(*start_x, *start_y, end_x, end_y, 3.0, color);
+        }
+        return;
+    }
+    let (left, top) = camera.grid_to_screen_position((min_x, min_y), cell_size);
+    let (right, bottom) = camera.grid_to_screen_position((max_x + 1, max_y + 1), cell_size);
+    draw_rectangle_lines(left, top, right - left, bottom - top, 3.0, color);
+}
+
+// Draw the painted cells and the ants of a pattern, faded, with its top left corner on a position
+pub fn draw_pattern_preview(
+    camera: &CCamera,
+    pattern: &Pattern,
+    position: (i32, i32),
+    palette: &[[u8; 4]],
+) {
+    let cell_size = camera.get_cell_size();
+    let scaled_size = camera.get_scaled_cell_size();
+    let lattice = camera.get_lattice();
+    for ((x, y), state) in pattern.get_cells() {
+        let Some([red, green, blue, _]) = palette.get(*state as usize).filter(|_| *state != 0)
+        else {
+            continue;
+        };
+        let color = Color::from_rgba(*red, *green, *blue, 128);
+        let cell = (position.0 + x, position.1 + y);
+        if lattice == Lattice::Hexagonal {
+            let (center_x, center_y) = camera.grid_to_screen_center(cell, cell_size);
+            draw_hexagon(
+                center_x,
+                center_y,
+                scaled_size / 2.0,
+                0.0,
+                false,
+                color,
+                color,
+            );
+        } else {
+            let (left, top) = camera.grid_to_screen_position(cell, cell_size);
+            draw_rectangle(left, top, scaled_size, scaled_size, color);
+        }
+    }
+    for ant in pattern.get_ants() {
+        let color = Color {
+            a: 0.5,
+            ..ant_color(lattice, ant.heading)
+        };
+        let cell = (position.0 + ant.x, position.1 + ant.y);
+        draw_ant(camera, cell, ant.heading, scaled_size / 2.0, color);
+    }
+
+    let (width, height) = pattern.get_size();
+    draw_selection(
+        camera,
+        (
+            position.0,
+            position.1,
+            position.0 + width - 1,
+            position.1 + height - 1,
+        ),
+    );
+}
+
+// Draw the painted hexagonal cells of a region one by one, there is no texture for this lattice
+pub fn draw_hex_cells(
+    camera: &CCamera,
+    grid: &ChunkedGrid,
+    palette: &[[u8; 4]],
+    visible_range: (i32, i32, i32, i32),
+) {
+    let (start_x, start_y, end_x, end_y) = visible_range;
+    let cell_size = camera.get_cell_size();
+    let radius = camera.get_scaled_cell_size() / 2.0;
+
+    for (position, state) in grid.iter_region(start_x, start_y, end_x, end_y) {
+        let (center_x, center_y) = camera.grid_to_screen_center(position, cell_size);
+        // The axial range is wider than the screen
+        if center_x < -radius
+            || center_y < -radius
+            || center_x > screen_width() + radius
+            || center_y > screen_height() + radius
+        {
+            continue;
+        }
+        let [red, green, blue, alpha] = *palette.get(state as usize).unwrap_or(&[255, 0, 255, 255]);
+        draw_hexagon(
+            center_x,
+            center_y,
+            radius,
+            0.0,
+            false,
+            BLANK,
+            Color::from_rgba(red, green, blue, alpha),
+        );
+    }
+}
+
+// Triangle over a cell, pointing toward the cell an ant with this heading moves to next
+pub fn draw_ant(camera: &CCamera, position: (i32, i32), heading: u8, size: f32, color: Color) {
+    let cell_size = camera.get_cell_size();
+    let lattice = camera.get_lattice();
+    let (screen_x, screen_y) = camera.grid_to_screen_center(position, cell_size);
+    // An ant facing past the edge of the grid points away from the cell behind it
+    let (next_x, next_y) = match lattice.advance(position, heading) {
+        Some(next) => camera.grid_to_screen_center(next, cell_size),
+        None => {
+            let degree = lattice.get_degree();
+            let behind = lattice
+                .advance(position, (heading + degree / 2) % degree)
+                .unwrap_or(position);
+            let (behind_x, behind_y) = camera.grid_to_screen_center(behind, cell_size);
+            (2.0 * screen_x - behind_x, 2.0 * screen_y - behind_y)
+        }
+    };
+    let center = Vec2::new(screen_x, screen_y);
+    let forward = Vec2::new(next_x - screen_x, next_y - screen_y).normalize_or_zero();
+    let side = forward.perp();
+
+    draw_triangle(
+        center + forward * size,
+        center - (forward + side) * size,
+        center - (forward - side) * size,
+        color,
+    );
+}
+
+// Color of an ant depending on its heading
+pub fn ant_color(lattice: Lattice, heading: u8) -> Color {
+    match lattice {
+        Lattice::Square => [RED, GREEN, BLUE, YELLOW][heading as usize % 4],
+        Lattice::Hexagonal => [RED, ORANGE, GREEN, BLUE, PURPLE, YELLOW][heading as usize % 6],
+    }
+}
+
+// RGBA color of every state of a gamemode, the background state 0 stays transparent
+pub fn build_palette(colors: &[u32]) -> Vec<[u8; 4]> {
+    colors
+        .iter()
+        .enumerate()
+        .map(|(state, color)| {
+            if state == 0 {
+                return [0; 4];
+            }
+            [(color >> 16) as u8, (color >> 8) as u8, *color as u8, 255]
+        })
+        .collect()
+}
+
+// Smallest power of two of cells sharing a pixel side at the given on-screen cell size
+pub fn cells_per_pixel(scaled_cell_size: f32) -> i32 {
+    let mut scale = 1;
+    while (scale as f32) * scaled_cell_size < 1.0 && scale < CHUNK_SIZE {
+        scale *= 2;
+    }
+    scale
+}
+
+// Color of the block of scale x scale cells shown by one pixel of a chunk tile
+pub fn block_color(
+    chunk: &Chunk,
+    palette: &[[u8; 4]],
+    scale: i32,
+    pixel_x: usize,
+    pixel_y: usize,
+) -> [u8; 4] {
+    let cells = chunk.get_cells();
+    let scale = scale as usize;
+    let chunk_size = CHUNK_SIZE as usize;
+    let missing = [255, 0, 255, 255];
+
+    if scale == 1 {
+        let state = cells[pixel_y * chunk_size + pixel_x] as usize;
+        return *palette.get(state).unwrap_or(&missing);
+    }
+
+    // Average the painted cells, the transparent background lowers the opacity
+    let mut sum = [0u32; 3];
+    let mut painted = 0u32;
+    for y in pixel_y * scale..(pixel_y + 1) * scale {
+        for x in pixel_x * scale..(pixel_x + 1) * scale {
+            let state = cells[y * chunk_size + x] as usize;
+            if state == 0 {
+                continue;
+            }
+            let color = palette.get(state).unwrap_or(&missing);
+            sum[0] += color[0] as u32;
+            sum[1] += color[1] as u32;
+            sum[2] += color[2] as u32;
+            painted += 1;
+        }
+    }
+    if painted == 0 {
+        return [0; 4];
+    }
+    [
+        (sum[0] / painted) as u8,
+        (sum[1] / painted) as u8,
+        (sum[2] / painted) as u8,
+        (painted * 255 / (scale * scale) as u32) as u8,
+    ]
+}
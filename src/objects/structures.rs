@@ -1,7 +1,14 @@
-use crate::gamemodes::langton::*;
+use crate::config::functions::key_name;
+use crate::config::structures::Keybindings;
+use crate::export::structures::Recorder;
 use crate::objects::functions::*;
+use langton_engine::gamemodes::Gamemode;
+use langton_engine::gamemodes::langton::Lattice;
+use langton_engine::grid::{CHUNK_SIZE, Chunk, ChunkedGrid};
+use langton_engine::pattern::Pattern;
 
 use macroquad::prelude::*;
+use std::collections::HashMap;
 use thousands::Separable;
 
 pub struct CCamera {
@@ -10,6 +17,8 @@ pub struct CCamera {
     cell_size: usize,
     zoom: f32,
     speed: f32,
+    // Cell layout of the current rule, hexagonal cells are flat-topped with axial coordinates
+    lattice: Lattice,
 }
 
 impl CCamera {
@@ -20,17 +29,10 @@ impl CCamera {
             cell_size: 10,
             zoom: 3.0,
             speed: 5.0,
+            lattice: Lattice::Square,
         }
     }
 
-    pub fn get_x(&self) -> f32 {
-        self.x
-    }
-
-    pub fn get_y(&self) -> f32 {
-        self.y
-    }
-
     pub fn get_zoom(&self) -> f32 {
         self.zoom
     }
@@ -43,6 +45,14 @@ impl CCamera {
         self.cell_size as f32 * self.zoom
     }
 
+    pub fn get_lattice(&self) -> Lattice {
+        self.lattice
+    }
+
+    pub fn set_lattice(&mut self, lattice: Lattice) {
+        self.lattice = lattice;
+    }
+
     /// Sets a shift in the both axis of the Camera, based on the zoom
     pub fn move_camera(&mut self, direction_x: f32, direction_y: f32) {
         // Calculate the adjusted speed based on zoom
@@ -63,12 +73,7 @@ impl CCamera {
         self.zoom *= value;
 
         // Clamp the zoom level to the desired range
-        if self.zoom > 20.0 {
-            self.zoom = 20.0;
-        }
-        if self.zoom < 0.2 {
-            self.zoom = 0.2;
-        }
+        self.zoom = self.zoom.clamp(0.01, 20.0);
 
         // Calculate the new camera position to keep the cursor in the same world position
         self.x = world_x_before - cursor_x / self.zoom;
@@ -82,6 +87,10 @@ impl CCamera {
         let world_x = (x) / self.zoom + self.x;
         let world_y = (y) / self.zoom + self.y;
 
+        if self.lattice == Lattice::Hexagonal {
+            return hex_world_to_axial((world_x, world_y), cell_size as f32);
+        }
+
         // Convert world coordinates to grid coordinates
         let grid_x = (world_x / cell_size as f32).floor() as i32;
         let grid_y = (world_y / cell_size as f32).floor() as i32;
@@ -89,6 +98,7 @@ impl CCamera {
         (grid_x, grid_y)
     }
 
+    // Top left corner of the box around a cell
     pub fn grid_to_screen_position(
         &self,
         grid_coordinates: (i32, i32),
@@ -97,8 +107,13 @@ impl CCamera {
         let (grid_x, grid_y) = grid_coordinates;
 
         // Convert grid position to world position
-        let world_x = grid_x as f32 * cell_size as f32;
-        let world_y = grid_y as f32 * cell_size as f32;
+        let (world_x, world_y) = match self.lattice {
+            Lattice::Square => (
+                grid_x as f32 * cell_size as f32,
+                grid_y as f32 * cell_size as f32,
+            ),
+            Lattice::Hexagonal => hex_axial_to_world((grid_x, grid_y), cell_size as f32),
+        };
 
         // Apply camera offset and zoom
         let screen_x = (world_x - self.x) * self.zoom;
@@ -107,116 +122,619 @@ impl CCamera {
         (screen_x, screen_y)
     }
 
+    pub fn grid_to_screen_center(
+        &self,
+        grid_coordinates: (i32, i32),
+        cell_size: usize,
+    ) -> (f32, f32) {
+        let (screen_x, screen_y) = self.grid_to_screen_position(grid_coordinates, cell_size);
+        let (width, height) = match self.lattice {
+            Lattice::Square => (cell_size as f32, cell_size as f32),
+            Lattice::Hexagonal => (cell_size as f32, cell_size as f32 * HEX_HEIGHT_RATIO),
+        };
+        (
+            screen_x + width * self.zoom / 2.0,
+            screen_y + height * self.zoom / 2.0,
+        )
+    }
+
     pub fn get_visible_range(&self, cell_size: f32) -> (i32, i32, i32, i32) {
-        let start_x = (self.x / (cell_size as f32)).floor() as i32;
-        let start_y = (self.y / (cell_size as f32)).floor() as i32;
-        let end_x = ((self.x + screen_width() / self.zoom) / (cell_size as f32)).ceil() as i32;
-        let end_y = ((self.y + screen_height() / self.zoom) / (cell_size as f32)).ceil() as i32;
+        let end_world_x = self.x + screen_width() / self.zoom;
+        let end_world_y = self.y + screen_height() / self.zoom;
+        if self.lattice == Lattice::Hexagonal {
+            // Columns are 3/4 of a cell apart and shift half a cell down each,
+            // the range covers the parallelogram of axial coordinates around the screen
+            let column = cell_size * 0.75;
+            let row = cell_size * HEX_HEIGHT_RATIO;
+            let start_q = (self.x / column).floor() as i32 - 1;
+            let end_q = (end_world_x / column).ceil() as i32;
+            let start_r = (self.y / row).floor() as i32 - (end_q + 1) / 2 - 1;
+            let end_r = (end_world_y / row).ceil() as i32 - start_q / 2 + 1;
+            return (start_q, start_r, end_q, end_r);
+        }
+        let start_x = (self.x / cell_size).floor() as i32;
+        let start_y = (self.y / cell_size).floor() as i32;
+        let end_x = (end_world_x / cell_size).ceil() as i32;
+        let end_y = (end_world_y / cell_size).ceil() as i32;
         (start_x, start_y, end_x, end_y)
     }
 }
 
-pub struct LangtonRenderer {}
+/// Keeps the cells of the visible chunks in a texture drawn as a single quad.
+/// Only the chunks modified since the previous frame are redrawn into the image.
+pub struct CellTexture {
+    image: Image,
+    texture: Option<Texture2D>,
+    // First chunk covered by the image and number of chunks on each axis
+    origin: (i32, i32),
+    chunks: (i32, i32),
+    // Cells per pixel side, above 1 when zoomed out past one pixel per cell
+    scale: i32,
+    palette: Vec<[u8; 4]>,
+    chunk_versions: HashMap<(i32, i32), u64>,
+    grid_version: u64,
+}
+
+impl CellTexture {
+    pub fn new() -> Self {
+        CellTexture {
+            image: Image::empty(),
+            texture: None,
+            origin: (0, 0),
+            chunks: (0, 0),
+            scale: 1,
+            palette: Vec::new(),
+            chunk_versions: HashMap::new(),
+            grid_version: 0,
+        }
+    }
+
+    pub fn draw(&mut self, camera: &CCamera, gamestate: &dyn Gamemode) {
+        let cell_size = camera.get_cell_size();
+        let (start_x, start_y, end_x, end_y) = camera.get_visible_range(cell_size as f32);
+        let (min_chunk, _) = ChunkedGrid::split_position((start_x, start_y));
+        let (max_chunk, _) = ChunkedGrid::split_position((end_x, end_y));
+        let chunks = (max_chunk.0 - min_chunk.0 + 1, max_chunk.1 - min_chunk.1 + 1);
+        let scale = cells_per_pixel(camera.get_scaled_cell_size());
+        let palette = build_palette(&gamestate.get_state_colors());
+        let grid = gamestate.get_grid();
+
+        // Start from a blank image whenever the covered area or the colors change
+        let rebuild = min_chunk != self.origin
+            || chunks != self.chunks
+            || scale != self.scale
+            || palette != self.palette;
+        if rebuild {
+            let tile = (CHUNK_SIZE / scale) as u16;
+            self.image =
+                Image::gen_image_color(chunks.0 as u16 * tile, chunks.1 as u16 * tile, BLANK);
+            self.origin = min_chunk;
+            self.chunks = chunks;
+            self.scale = scale;
+            self.palette = palette;
+            self.chunk_versions.clear();
+        } else if grid.get_version() == self.grid_version {
+            self.draw_quad(camera);
+            return;
+        }
+        self.grid_version = grid.get_version();
+
+        let mut changed = rebuild;
+        for chunk_y in min_chunk.1..=max_chunk.1 {
+            for chunk_x in min_chunk.0..=max_chunk.0 {
+                let position = (chunk_x, chunk_y);
+                let chunk = grid.get_chunk(position);
+                let version = chunk.as_ref().map_or(0, |chunk| chunk.get_version());
+                if version == self.chunk_versions.get(&position).copied().unwrap_or(0) {
+                    continue;
+                }
+                self.paint_chunk(position, chunk.as_deref());
+                if version == 0 {
+                    self.chunk_versions.remove(&position);
+                } else {
+                    self.chunk_versions.insert(position, version);
+                }
+                changed = true;
+            }
+        }
+
+        if changed {
+            match &self.texture {
+                Some(texture)
+                    if texture.width() as u16 == self.image.width
+                        && texture.height() as u16 == self.image.height =>
+                {
+                    texture.update(&self.image)
+                }
+                _ => {
+                    let texture = Texture2D::from_image(&self.image);
+                    texture.set_filter(FilterMode::Nearest);
+                    self.texture = Some(texture);
+                }
+            }
+        }
+        self.draw_quad(camera);
+    }
+
+    fn draw_quad(&self, camera: &CCamera) {
+        let Some(texture) = &self.texture else {
+            return;
+        };
+        let cell_size = camera.get_cell_size();
+        let (screen_x, screen_y) = camera.grid_to_screen_position(
+            (self.origin.0 * CHUNK_SIZE, self.origin.1 * CHUNK_SIZE),
+            cell_size,
+        );
+        let scaled_chunk_size = CHUNK_SIZE as f32 * camera.get_scaled_cell_size();
+        draw_texture_ex(
+            texture,
+            screen_x,
+            screen_y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(
+                    self.chunks.0 as f32 * scaled_chunk_size,
+                    self.chunks.1 as f32 * scaled_chunk_size,
+                )),
+                ..Default::default()
+            },
+        );
+    }
+
+    // Redraw the pixels of one chunk, a missing chunk is fully transparent
+    fn paint_chunk(&mut self, position: (i32, i32), chunk: Option<&Chunk>) {
+        let tile = (CHUNK_SIZE / self.scale) as usize;
+        let left = (position.0 - self.origin.0) as usize * tile;
+        let top = (position.1 - self.origin.1) as usize * tile;
+        let width = self.image.width as usize;
+
+        for pixel_y in 0..tile {
+            for pixel_x in 0..tile {
+                let color = match chunk {
+                    Some(chunk) => block_color(chunk, &self.palette, self.scale, pixel_x, pixel_y),
+                    None => [0; 4],
+                };
+                let offset = ((top + pixel_y) * width + left + pixel_x) * 4;
+                self.image.bytes[offset..offset + 4].copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+// Seconds a message stays on screen
+const MESSAGE_DURATION: f64 = 6.0;
+
+/// Short-lived messages shown at the bottom left of the screen, also echoed to the terminal
+pub struct MessageLog {
+    messages: Vec<(String, f64)>,
+}
+
+impl MessageLog {
+    pub fn new() -> Self {
+        MessageLog {
+            messages: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, message: &str) {
+        eprintln!("{}", message);
+        self.messages.push((message.to_string(), get_time()));
+    }
+
+    pub fn draw(&mut self) {
+        let now = get_time();
+        self.messages
+            .retain(|(_, shown_at)| now - shown_at < MESSAGE_DURATION);
+
+        for (line, (message, _)) in self.messages.iter().rev().enumerate() {
+            draw_text(
+                message,
+                10.0,
+                screen_height() - 16.0 - line as f32 * 30.0,
+                30.0,
+                RED,
+            );
+        }
+    }
+}
+
+/// List of gamemodes drawn over the simulation, an entry is picked with its number key
+pub struct GamemodeMenu {
+    open: bool,
+}
+
+impl GamemodeMenu {
+    pub fn new() -> Self {
+        GamemodeMenu { open: false }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    pub fn draw(&self, gamemodes: &[Box<dyn Gamemode>], active: usize) {
+        if !self.open {
+            return;
+        }
+        let height = 80.0 + gamemodes.len() as f32 * 50.0;
+        let top = (screen_height() - height) / 2.0;
+        draw_rectangle(
+            screen_width() / 2.0 - 300.0,
+            top,
+            600.0,
+            height,
+            Color::new(0.0, 0.0, 0.0, 0.8),
+        );
+        draw_text(
+            "Gamemodes",
+            screen_width() / 2.0 - 280.0,
+            top + 50.0,
+            45.0,
+            WHITE,
+        );
+        for (index, gamemode) in gamemodes.iter().enumerate() {
+            let color = if index == active { YELLOW } else { WHITE };
+            draw_text(
+                &format!("{}. {}", index + 1, gamemode.get_mode_name()),
+                screen_width() / 2.0 - 260.0,
+                top + 100.0 + index as f32 * 50.0,
+                40.0,
+                color,
+            );
+        }
+    }
+}
+
+/// What the left mouse button does on the grid
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Tool {
+    // Placement of the gamemode, ants in Langton and live cells in Life
+    Place,
+    Paint,
+    Erase,
+    Fill,
+    // Drag a rectangle to copy
+    Select,
+    // Stamp the copied pattern
+    Paste,
+}
+
+impl Tool {
+    pub fn get_name(&self) -> &str {
+        match self {
+            Tool::Place => "Place",
+            Tool::Paint => "Paint",
+            Tool::Erase => "Erase",
+            Tool::Fill => "Fill",
+            Tool::Select => "Select",
+            Tool::Paste => "Paste",
+        }
+    }
+
+    pub fn next(&self) -> Tool {
+        match self {
+            Tool::Place => Tool::Paint,
+            Tool::Paint => Tool::Erase,
+            Tool::Erase => Tool::Fill,
+            Tool::Fill => Tool::Select,
+            Tool::Select => Tool::Paste,
+            Tool::Paste => Tool::Place,
+        }
+    }
+}
+
+/// Tool of the mouse and state written by the paint and fill tools
+pub struct Brush {
+    tool: Tool,
+    paint_state: u8,
+}
+
+impl Brush {
+    pub fn new() -> Self {
+        Brush {
+            tool: Tool::Place,
+            paint_state: 1,
+        }
+    }
+
+    pub fn get_tool(&self) -> Tool {
+        self.tool
+    }
+
+    pub fn set_tool(&mut self, tool: Tool) {
+        self.tool = tool;
+    }
+
+    // State painted with a rule of state_count states, the last one when the rule is shorter
+    pub fn get_paint_state(&self, state_count: usize) -> u8 {
+        self.paint_state
+            .min(state_count.saturating_sub(1).min(u8::MAX as usize) as u8)
+    }
+
+    // Cycle through the states of the rule, skipping the background
+    pub fn next_paint_state(&mut self, state_count: usize) {
+        let current = self.get_paint_state(state_count) as usize;
+        self.paint_state = if current + 1 < state_count {
+            current as u8 + 1
+        } else {
+            1
+        };
+    }
+}
+
+/// Selected rectangle of cells and the pattern last copied
+pub struct Clipboard {
+    // Cell where the selection drag started
+    anchor: Option<(i32, i32)>,
+    selection: Option<(i32, i32, i32, i32)>,
+    pattern: Option<Pattern>,
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        Clipboard {
+            anchor: None,
+            selection: None,
+            pattern: None,
+        }
+    }
+
+    pub fn start_selection(&mut self, position: (i32, i32)) {
+        self.anchor = Some(position);
+        self.selection = Some((position.0, position.1, position.0, position.1));
+    }
+
+    // Stretch the selection from its anchor to a position while the drag goes on
+    pub fn extend_selection(&mut self, position: (i32, i32)) {
+        if let Some((x, y)) = self.anchor {
+            self.selection = Some((
+                x.min(position.0),
+                y.min(position.1),
+                x.max(position.0),
+                y.max(position.1),
+            ));
+        }
+    }
+
+    pub fn end_selection(&mut self) {
+        self.anchor = None;
+    }
+
+    // Inclusive rectangle (min_x, min_y, max_x, max_y) of the selection
+    pub fn get_selection(&self) -> Option<(i32, i32, i32, i32)> {
+        self.selection
+    }
+
+    pub fn get_pattern(&self) -> Option<&Pattern> {
+        self.pattern.as_ref()
+    }
+
+    pub fn get_pattern_mut(&mut self) -> Option<&mut Pattern> {
+        self.pattern.as_mut()
+    }
+
+    pub fn set_pattern(&mut self, pattern: Pattern) {
+        self.pattern = Some(pattern);
+    }
+}
+
+/// Lists the key of every action, a selected action takes the next key pressed
+pub struct KeybindingMenu {
+    open: bool,
+    selected: usize,
+    // Waiting for the key of the selected action
+    capturing: bool,
+}
+
+impl KeybindingMenu {
+    pub fn new() -> Self {
+        KeybindingMenu {
+            open: false,
+            selected: 0,
+            capturing: false,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.capturing = false;
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.capturing
+    }
+
+    pub fn set_capturing(&mut self, value: bool) {
+        self.capturing = value;
+    }
+
+    pub fn get_selected(&self) -> usize {
+        self.selected
+    }
+
+    // Move the selection, wrapping around the list of actions
+    pub fn move_selection(&mut self, offset: isize, action_count: usize) {
+        self.selected =
+            (self.selected as isize + offset).rem_euclid(action_count as isize) as usize;
+    }
+
+    pub fn draw(&self, keybindings: &Keybindings) {
+        if !self.open {
+            return;
+        }
+        let bindings = keybindings.get_bindings();
+        let width = 700.0;
+        let height = screen_height() - 100.0;
+        let left = (screen_width() - width) / 2.0;
+        draw_rectangle(left, 50.0, width, height, Color::new(0.0, 0.0, 0.0, 0.8));
+        draw_text(
+            &format!(
+                "Keybindings ({})",
+                keybindings.get_preset().get_name().to_uppercase()
+            ),
+            left + 20.0,
+            100.0,
+            45.0,
+            WHITE,
+        );
+        draw_text(
+            "Enter: rebind  Delete: unbind  Left/Right: preset  B: close",
+            left + 20.0,
+            140.0,
+            25.0,
+            GRAY,
+        );
+
+        // Scroll to keep the selected action in view
+        let row_height = 34.0;
+        let rows = (((height - 120.0) / row_height) as usize).max(1);
+        let first = self
+            .selected
+            .saturating_sub(rows / 2)
+            .min(bindings.len().saturating_sub(rows));
+        for (row, (action, key)) in bindings.iter().enumerate().skip(first).take(rows) {
+            let y = 185.0 + (row - first) as f32 * row_height;
+            let color = if row == self.selected { YELLOW } else { WHITE };
+            let key_text = if row == self.selected && self.capturing {
+                "press a key...".to_string()
+            } else {
+                key.map(key_name).unwrap_or_else(|| "-".to_string())
+            };
+            draw_text(&action.get_name(), left + 40.0, y, 32.0, color);
+            draw_text(&key_text, left + 420.0, y, 32.0, color);
+        }
+    }
+}
+
+pub struct LangtonRenderer {
+    // Iterations per second, derived from the frame rate by the frontend
+    update_speed: u64,
+    cells: CellTexture,
+}
 
 impl LangtonRenderer {
     // Constructor for LangtonRenderer
     pub fn new() -> Self {
-        Self {}
+        Self {
+            update_speed: 0,
+            cells: CellTexture::new(),
+        }
+    }
+
+    pub fn set_update_speed(&mut self, value: u64) {
+        self.update_speed = value;
+    }
+
+    // Forget the cached cells, the next frame redraws them from a different grid
+    pub fn invalidate(&mut self) {
+        self.cells = CellTexture::new();
     }
 
     /// Draws graphical elements
-    pub fn render(&mut self, camera: &CCamera, gamestate: &Gamestate) {
-        let zoom = camera.get_zoom();
+    pub fn render(
+        &mut self,
+        camera: &CCamera,
+        gamestate: &dyn Gamemode,
+        brush: &Brush,
+        clipboard: &Clipboard,
+    ) {
         let cell_size = camera.get_cell_size();
-        let scaled_cell_size = camera.get_scaled_cell_size();
         let (start_x, start_y, end_x, end_y) = camera.get_visible_range(cell_size as f32);
 
-        // Draw the grid
-        draw_cell_grid(camera, start_x, start_y, end_x, end_y);
+        let lattice = camera.get_lattice();
+        if lattice == Lattice::Square {
+            // Draw the grid
+            draw_cell_grid(camera, start_x, start_y, end_x, end_y);
+
+            // Draw cells
+            self.cells.draw(camera, gamestate);
+        } else {
+            // Hexagons are drawn without grid lines
+            let palette = build_palette(&gamestate.get_state_colors());
+            draw_hex_cells(
+                camera,
+                gamestate.get_grid(),
+                &palette,
+                (start_x, start_y, end_x, end_y),
+            );
+        }
 
-        // Draw cells
-        for ((x, y), state) in gamestate
-            .get_grid()
-            .iter()
-            .filter(|((x, y), _)| *x >= start_x && *x <= end_x && *y >= start_y && *y <= end_y)
-            .map(|((x, y), state)| ((*x, *y), *state))
+        if let Some(world) = gamestate
+            .as_langton()
+            .and_then(|langton| langton.get_world())
         {
-            let screen_x = (x as f32 * cell_size as f32 - camera.get_x()) * zoom;
-            let screen_y = (y as f32 * cell_size as f32 - camera.get_y()) * zoom;
+            draw_world_border(camera, &world);
+        }
 
-            draw_rectangle(
-                screen_x,
-                screen_y,
-                scaled_cell_size,
-                scaled_cell_size,
-                *gamestate.get_rule().get_rule_color(state as usize),
+        // Draw ants in visible region, pointing toward the cell they move to next
+        let ant_size = camera.get_scaled_cell_size() / 2.0;
+        let ants = match gamestate.as_langton() {
+            Some(langton) => langton.get_ants_in_region(start_x, end_x, start_y, end_y),
+            None => Vec::new(),
+        };
+        for ant in &ants {
+            draw_ant(
+                camera,
+                (ant.x, ant.y),
+                ant.heading,
+                ant_size,
+                ant_color(lattice, ant.heading),
             );
         }
 
-        // Draw ants in visible region
-        for ant in &gamestate.get_ants_in_region(start_x, end_x, start_y, end_y) {
-            let screen_x = (ant.x as f32 * cell_size as f32 - camera.get_x()) * zoom
-                + cell_size as f32 * zoom / 2.0;
-            let screen_y = (ant.y as f32 * cell_size as f32 - camera.get_y()) * zoom
-                + cell_size as f32 * zoom / 2.0;
-
-            let ant_color = match ant.direction {
-                Direction::Up => RED,
-                Direction::Right => GREEN,
-                Direction::Down => BLUE,
-                Direction::Left => YELLOW,
-            };
+        // Selection and the pattern about to be pasted
+        if let Some(selection) = clipboard.get_selection() {
+            draw_selection(camera, selection);
+        }
+        if brush.get_tool() == Tool::Paste
+            && let Some(pattern) = clipboard.get_pattern()
+        {
+            let position = camera.screen_to_grid_position(mouse_position(), cell_size);
+            let palette = build_palette(&gamestate.get_state_colors());
+            draw_pattern_preview(camera, pattern, position, &palette);
+            return;
+        }
 
-            let ant_size = camera.get_scaled_cell_size() / 2.0;
-
-            let (p1_x, p1_y, p2_x, p2_y, p3_x, p3_y) = match ant.direction {
-                Direction::Up => (
-                    screen_x,
-                    screen_y - ant_size,
-                    screen_x - ant_size,
-                    screen_y + ant_size,
-                    screen_x + ant_size,
-                    screen_y + ant_size,
-                ),
-                Direction::Right => (
-                    screen_x + ant_size,
-                    screen_y,
-                    screen_x - ant_size,
-                    screen_y - ant_size,
-                    screen_x - ant_size,
-                    screen_y + ant_size,
-                ),
-                Direction::Down => (
-                    screen_x,
-                    screen_y + ant_size,
-                    screen_x - ant_size,
-                    screen_y - ant_size,
-                    screen_x + ant_size,
-                    screen_y - ant_size,
-                ),
-                Direction::Left => (
-                    screen_x - ant_size,
-                    screen_y,
-                    screen_x + ant_size,
-                    screen_y - ant_size,
-                    screen_x + ant_size,
-                    screen_y + ant_size,
-                ),
+        // Draw cursor, with the heading of the ants it places
+        let (cursor_x, cursor_y) = gamestate.get_cursor_dimensions();
+        draw_cursor(camera, cursor_x, cursor_y);
+        if let Some(langton) = gamestate.as_langton()
+            && brush.get_tool() == Tool::Place
+        {
+            let heading = langton.get_placement_heading();
+            let position = camera.screen_to_grid_position(mouse_position(), cell_size);
+            let color = Color {
+                a: 0.5,
+                ..ant_color(lattice, heading)
             };
-
-            draw_triangle(
-                Vec2::new(p1_x, p1_y),
-                Vec2::new(p2_x, p2_y),
-                Vec2::new(p3_x, p3_y),
-                ant_color,
-            );
+            for offset_x in 0..cursor_x as i32 {
+                for offset_y in 0..cursor_y as i32 {
+                    let cell = (position.0 + offset_x, position.1 + offset_y);
+                    draw_ant(camera, cell, heading, ant_size, color);
+                }
+            }
         }
-
-        // Draw cursor
-        draw_cursor(&camera, gamestate.get_cursor_x(), gamestate.get_cursor_y());
     }
 
-    pub fn draw_texts(&self, camera: &CCamera, gamestate: &Gamestate) {
+    pub fn draw_texts(
+        &self,
+        camera: &CCamera,
+        gamestate: &dyn Gamemode,
+        brush: &Brush,
+        recorder: Option<&Recorder>,
+    ) {
         // Camera
         let camera_text = &format!("Zoom {:.2}x", camera.get_zoom());
         draw_text(
@@ -229,7 +747,7 @@ impl LangtonRenderer {
         // Cells
         let cell_text = &format!(
             "Cells:{}",
-            gamestate.get_grid().len().separate_with_spaces()
+            gamestate.get_painted_cells().separate_with_spaces()
         );
         draw_text(
             cell_text,
@@ -239,20 +757,102 @@ impl LangtonRenderer {
             DARKPURPLE,
         );
         // Ants
-        let ant_text = &format!(
-            "Ants:{}/{}",
-            gamestate
-                .get_total_visible_ants(camera.get_visible_range(camera.get_cell_size() as f32))
-                .separate_with_spaces(),
-            gamestate.get_total_ants().separate_with_spaces()
+        if let Some(langton) = gamestate.as_langton() {
+            let ant_text = &format!(
+                "Ants:{}/{}",
+                langton
+                    .get_total_visible_ants(camera.get_visible_range(camera.get_cell_size() as f32))
+                    .separate_with_spaces(),
+                langton.get_total_ants().separate_with_spaces()
+            );
+            draw_text(
+                ant_text,
+                screen_width() - measure_text(ant_text, None, 45, 1.0).width,
+                150.0,
+                45.0,
+                DARKPURPLE,
+            );
+        }
+        // Gamemode and rule
+        let rule_names = gamestate.get_rule_names();
+        let rule_text = &format!(
+            "{}: {} ({}/{})",
+            gamestate.get_mode_name(),
+            rule_names[gamestate.get_selected_rule()],
+            gamestate.get_selected_rule() + 1,
+            rule_names.len()
+        );
+        draw_text(
+            rule_text,
+            screen_width() - measure_text(rule_text, None, 45, 1.0).width,
+            200.0,
+            45.0,
+            DARKPURPLE,
         );
+        // Periodic behaviour of a lone ant
+        if let Some(cycle) = gamestate
+            .as_langton()
+            .and_then(|langton| langton.get_cycle())
+        {
+            let cycle_text = &if cycle.is_highway() {
+                format!(
+                    "Highway: {} steps, ({}, {})",
+                    cycle.period, cycle.displacement.0, cycle.displacement.1
+                )
+            } else {
+                format!("Cycle: {} steps", cycle.period)
+            };
+            draw_text(
+                cycle_text,
+                screen_width() - measure_text(cycle_text, None, 45, 1.0).width,
+                250.0,
+                45.0,
+                DARKPURPLE,
+            );
+        }
+        // Tool, with the color painted by the paint and fill tools
+        let colors = gamestate.get_state_colors();
+        let paint_state = brush.get_paint_state(colors.len());
+        let tool_text = &match (brush.get_tool(), gamestate.as_langton()) {
+            (Tool::Paint | Tool::Fill, _) => {
+                format!("{} state {}", brush.get_tool().get_name(), paint_state)
+            }
+            (Tool::Place, Some(langton)) => format!(
+                "Place facing {}",
+                langton
+                    .get_lattice()
+                    .get_heading_name(langton.get_placement_heading())
+            ),
+            (tool, _) => tool.get_name().to_string(),
+        };
+        let tool_width = measure_text(tool_text, None, 45, 1.0).width;
         draw_text(
-            ant_text,
-            screen_width() - measure_text(ant_text, None, 45, 1.0).width,
-            150.0,
+            tool_text,
+            screen_width() - tool_width,
+            350.0,
             45.0,
             DARKPURPLE,
         );
+        if matches!(brush.get_tool(), Tool::Paint | Tool::Fill) {
+            draw_rectangle(
+                screen_width() - tool_width - 45.0,
+                318.0,
+                35.0,
+                35.0,
+                Color::from_hex(colors.get(paint_state as usize).copied().unwrap_or(0)),
+            );
+        }
+        // Recording
+        if let Some(recorder) = recorder {
+            let record_text = &format!("Recording: {} frames", recorder.get_frames());
+            draw_text(
+                record_text,
+                screen_width() - measure_text(record_text, None, 45, 1.0).width,
+                300.0,
+                45.0,
+                RED,
+            );
+        }
         // Mouse
         draw_text(
             &format!(
@@ -268,15 +868,21 @@ impl LangtonRenderer {
         // FPS
         draw_text(&format!("{}", get_fps()), 10.0, 42.0, 42.0, RED);
         // Iteration
+        // A reversed simulation shows a negative speed
+        let direction = match gamestate.as_langton() {
+            Some(langton) if langton.get_reverse_state() => "-",
+            _ => "",
+        };
         let iteration_text = &format!(
-            "Iter:{} at {} ({}/s)",
+            "Iter:{} at {}{} ({}/s)",
             gamestate.get_iteration().separate_with_spaces(),
+            direction,
             gamestate.get_speed().separate_with_spaces(),
-            gamestate.get_update_speed().separate_with_spaces()
+            self.update_speed.separate_with_spaces()
         );
         draw_text(
             iteration_text,
-            (screen_width() - measure_text(&iteration_text, None, 42, 1.0).width) / 2.0,
+            (screen_width() - measure_text(iteration_text, None, 42, 1.0).width) / 2.0,
             42.0,
             42.0,
             RED,